@@ -0,0 +1,96 @@
+// compares converting a batch of entities by value, which requires a clone when
+// the entities are still needed, with borrowing them through `to_item`. run
+// with `cargo bench --bench to_item`.
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use deez::*;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAlloc;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAlloc = CountingAlloc;
+
+#[derive(Debug, Clone, Deez)]
+#[deez_schema(table = "TaskTable", service = "TaskService", entity = "Task")]
+#[deez_schema(primary_hash = "pk", primary_range = "sk")]
+#[deez_schema(gsi1_name = "task_gsi1", gsi1_hash = "gsi1pk", gsi1_range = "gsi1sk")]
+pub struct Task {
+    #[deez_primary(key = "hash")]
+    #[deez_gsi1(key = "range")]
+    pub task_id: Option<String>,
+    #[deez_primary(key = "range")]
+    #[deez_gsi1(key = "hash")]
+    pub project: Option<String>,
+    pub description: String,
+    pub tags: Vec<String>,
+    pub points: u32,
+}
+
+const BATCH: usize = 1000;
+
+fn tasks() -> Vec<Task> {
+    (0..BATCH)
+        .map(|i| Task {
+            task_id: Some(format!("task-{}", i)),
+            project: Some("deez".to_string()),
+            description: "nothin' but chillin' 20's".to_string(),
+            tags: vec!["foo".to_string(), "bar".to_string()],
+            points: i as u32,
+        })
+        .collect()
+}
+
+fn clone_into(tasks: &[Task]) -> Vec<HashMap<String, AttributeValue>> {
+    tasks.iter().map(|t| t.clone().into()).collect()
+}
+
+fn to_item(tasks: &[Task]) -> Vec<HashMap<String, AttributeValue>> {
+    tasks.iter().map(Task::to_item).collect()
+}
+
+fn allocations(f: impl FnOnce() -> Vec<HashMap<String, AttributeValue>>) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(f());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn bench(c: &mut Criterion) {
+    let tasks = tasks();
+
+    println!(
+        "allocations for {} items: clone + into {}, to_item {}",
+        BATCH,
+        allocations(|| clone_into(&tasks)),
+        allocations(|| to_item(&tasks)),
+    );
+
+    let mut group = c.benchmark_group("batch");
+    group.bench_function("clone_into", |b| b.iter(|| clone_into(black_box(&tasks))));
+    group.bench_function("to_item", |b| b.iter(|| to_item(black_box(&tasks))));
+    group.finish();
+}
+
+criterion_group!(benches, bench);
+criterion_main!(benches);
@@ -1,23 +1,29 @@
 mod macros;
 mod types;
 
-use crate::types::{Composite, FieldType, IndexKey, IndexKeys};
+use crate::types::{Composite, Conversion, FieldType, IndexKey, IndexKeys, KeyEncoding, KeyFormat};
 use attribute_derive::Attribute;
-use macros::{attr_derive, compose_key, insert_gsi, insert_index, insert_read, read_attr};
+use macros::{attr_derive, compose_key, from_av, from_key_part, insert_gsi, insert_index, insert_read, key_part, read_attr, unexpected_variant};
 use proc_macro::{self, TokenStream};
-use quote::{format_ident, quote};
+use quote::{format_ident, quote, quote_spanned};
 use std::{collections::HashMap, fmt::Debug};
-use syn::DeriveInput;
+use syn::{parse_macro_input, spanned::Spanned, DeriveInput};
 
 attr_derive!();
 
-// todo: no unwrap and use span for errors(?)
 #[proc_macro_derive(
     Deez,
     attributes(
         deez_schema,
+        deez_index,
+        deez_key,
         deez_ignore,
+        deez_field,
+        deez_default,
         deez_vec,
+        deez_time,
+        deez_enum,
+        deez_variant,
         deez_primary,
         deez_gsi1,
         deez_gsi2,
@@ -42,47 +48,164 @@ attr_derive!();
     )
 )]
 pub fn derive(input: TokenStream) -> TokenStream {
-    let DeriveInput { attrs, data, ident, .. } = syn::parse(input).unwrap();
+    let input = parse_macro_input!(input as DeriveInput);
+    match derive_deez(input) {
+        Ok(out) => out.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn derive_deez(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let DeriveInput { attrs, data, ident, vis, .. } = input;
 
     let struct_data = match data {
         syn::Data::Struct(s) => s,
-        _ => panic!("could not parse struct"),
+        syn::Data::Enum(e) => return derive_enum(ident, attrs, e),
+        syn::Data::Union(_) => return Err(syn::Error::new_spanned(ident, "Deez can not be derived for unions")),
     };
+    if !matches!(struct_data.fields, syn::Fields::Named(_)) {
+        return Err(syn::Error::new_spanned(
+            ident,
+            "Deez can only be derived for structs with named fields",
+        ));
+    }
 
     let mut index_meta = HashMap::new();
     let mut index_name_fns = quote! {};
     let mut index_key_fns = quote! {};
     let mut index_keys_fns = quote! {};
+    let mut index_parse_fns = quote! {};
     let mut index_inserts = quote! {};
     let mut struct_impl = quote! {};
+    // attributes written to an item, to pre-size the map
+    let mut attribute_count = 0usize;
 
-    if let Ok(s) = DeezSchema::from_attributes(&attrs) {
-        insert_index!(index_meta, "primary", s.primary_hash, s.primary_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi1", s.gsi1_name, s.gsi1_hash, s.gsi1_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi2", s.gsi2_name, s.gsi2_hash, s.gsi2_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi3", s.gsi3_name, s.gsi3_hash, s.gsi3_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi4", s.gsi4_name, s.gsi4_hash, s.gsi4_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi5", s.gsi5_name, s.gsi5_hash, s.gsi5_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi6", s.gsi6_name, s.gsi6_hash, s.gsi6_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi7", s.gsi7_name, s.gsi7_hash, s.gsi7_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi8", s.gsi8_name, s.gsi8_hash, s.gsi8_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi9", s.gsi9_name, s.gsi9_hash, s.gsi9_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi10", s.gsi10_name, s.gsi10_hash, s.gsi10_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi11", s.gsi11_name, s.gsi11_hash, s.gsi11_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi12", s.gsi12_name, s.gsi12_hash, s.gsi12_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi13", s.gsi13_name, s.gsi13_hash, s.gsi13_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi14", s.gsi14_name, s.gsi14_hash, s.gsi14_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi15", s.gsi15_name, s.gsi15_hash, s.gsi15_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi16", s.gsi16_name, s.gsi16_hash, s.gsi16_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi17", s.gsi17_name, s.gsi17_hash, s.gsi17_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi18", s.gsi18_name, s.gsi18_hash, s.gsi18_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi19", s.gsi19_name, s.gsi19_hash, s.gsi19_range);
-        insert_gsi!(index_meta, index_name_fns, "gsi20", s.gsi20_name, s.gsi20_hash, s.gsi20_range);
+    let s = DeezSchema::from_attributes(&attrs)?;
+    let rename_all = s.rename_all.as_deref();
+    if let Some(case) = rename_all {
+        if convert_case("", case).is_none() {
+            return Err(syn::Error::new(
+                schema_span(&attrs, "rename_all"),
+                format!("unknown rename_all rule `{}`", case),
+            ));
+        }
+    }
+
+    // a schema with only `rename_all` or `skip_none` can be used by structs nested in an entity
+    if s.table.is_some() || s.service.is_some() || s.entity.is_some() {
+        let required = |value: &Option<String>, key: &str| match value {
+            Some(value) => Ok(value.clone()),
+            None => Err(syn::Error::new(
+                schema_span(&attrs, "table"),
+                format!("`{}` not provided in deez_schema", key),
+            )),
+        };
+        let table = required(&s.table, "table")?;
+        let service = required(&s.service, "service")?;
+        let entity = required(&s.entity, "entity")?;
+        let primary_hash = required(&s.primary_hash, "primary_hash")?;
+        let key_format = key_format(&s, &attrs)?;
+
+        insert_index!(index_meta, "primary", primary_hash.clone(), s.primary_range);
+        set_templates(
+            &mut index_meta,
+            "primary",
+            s.primary_hash_template.map(|t| (t, schema_span(&attrs, "primary_hash_template"))),
+            s.primary_range_template.map(|t| (t, schema_span(&attrs, "primary_range_template"))),
+        )?;
+        set_key_types(
+            &mut index_meta,
+            "primary",
+            s.primary_hash_type.map(|t| (t, schema_span(&attrs, "primary_hash_type"))),
+            s.primary_range_type.map(|t| (t, schema_span(&attrs, "primary_range_type"))),
+        )?;
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi1", s.gsi1_name, s.gsi1_hash, s.gsi1_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi2", s.gsi2_name, s.gsi2_hash, s.gsi2_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi3", s.gsi3_name, s.gsi3_hash, s.gsi3_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi4", s.gsi4_name, s.gsi4_hash, s.gsi4_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi5", s.gsi5_name, s.gsi5_hash, s.gsi5_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi6", s.gsi6_name, s.gsi6_hash, s.gsi6_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi7", s.gsi7_name, s.gsi7_hash, s.gsi7_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi8", s.gsi8_name, s.gsi8_hash, s.gsi8_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi9", s.gsi9_name, s.gsi9_hash, s.gsi9_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi10", s.gsi10_name, s.gsi10_hash, s.gsi10_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi11", s.gsi11_name, s.gsi11_hash, s.gsi11_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi12", s.gsi12_name, s.gsi12_hash, s.gsi12_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi13", s.gsi13_name, s.gsi13_hash, s.gsi13_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi14", s.gsi14_name, s.gsi14_hash, s.gsi14_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi15", s.gsi15_name, s.gsi15_hash, s.gsi15_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi16", s.gsi16_name, s.gsi16_hash, s.gsi16_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi17", s.gsi17_name, s.gsi17_hash, s.gsi17_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi18", s.gsi18_name, s.gsi18_hash, s.gsi18_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi19", s.gsi19_name, s.gsi19_hash, s.gsi19_range);
+        insert_gsi!(index_meta, index_name_fns, attrs, "gsi20", s.gsi20_name, s.gsi20_hash, s.gsi20_range);
+
+        for attr in attrs.iter().filter(|a| a.path().is_ident("deez_index")) {
+            let index = DeezIndex::from_attributes([attr])?;
+            if syn::parse_str::<syn::Ident>(&index.name).is_err() {
+                return Err(syn::Error::new_spanned(attr, "index name must be a valid identifier"));
+            }
+            if index_meta.contains_key(&index.name) {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    format!("index `{}` is already declared", index.name),
+                ));
+            }
+            // local indexes share the primary hash key and only declare a range
+            let hash = match (index.local, index.hash) {
+                (false, Some(hash)) => hash,
+                (false, None) => {
+                    return Err(syn::Error::new_spanned(attr, "`hash` not provided in deez_index"));
+                }
+                (true, None) if index.hash_template.is_none() && index.hash_type.is_none() => {
+                    if index_meta["primary"].range.is_none() {
+                        return Err(syn::Error::new_spanned(
+                            attr,
+                            "local secondary indexes require `primary_range` in deez_schema",
+                        ));
+                    }
+                    if index.range.is_none() {
+                        return Err(syn::Error::new_spanned(attr, "local secondary indexes require a `range` key"));
+                    }
+                    primary_hash.clone()
+                }
+                (true, _) => {
+                    return Err(syn::Error::new_spanned(attr, "local secondary indexes share the primary hash key"));
+                }
+            };
+            insert_index!(index_meta, index.name, hash, index.range);
+            index_meta.get_mut(&index.name).unwrap().local = index.local;
+            index_meta.get_mut(&index.name).unwrap().sparse = index.sparse;
+            set_templates(
+                &mut index_meta,
+                &index.name,
+                index.hash_template.map(|t| (t, attr.span())),
+                index.range_template.map(|t| (t, attr.span())),
+            )?;
+            set_key_types(
+                &mut index_meta,
+                &index.name,
+                index.hash_type.map(|t| (t, attr.span())),
+                index.range_type.map(|t| (t, attr.span())),
+            )?;
+            let index_name_fn_name = format_ident!("{}_name", index.name);
+            let index_name = index.index;
+            index_name_fns = quote! {
+                #index_name_fns
+                pub fn #index_name_fn_name() -> String {
+                    #index_name.to_string()
+                }
+            };
+        }
 
         for field in struct_data.fields.iter() {
             // todo: remove length check?
-            if field.attrs.len() > 0 {
-                read_attr!(index_meta, field, DeezPrimary, "primary");
+            if !field.attrs.is_empty() {
+                if let Some(attr) = field.attrs.iter().find(|a| a.path().is_ident("deez_primary")) {
+                    let primary = DeezPrimary::from_attributes(&field.attrs)?;
+                    let encoding = key_encoding(field, attr, primary.pad, primary.sortable, primary.time)?;
+                    add_composite(&mut index_meta, field, attr, "primary", &primary.key, primary.position, encoding)?;
+                }
                 read_attr!(index_meta, field, DeezGsi1, "gsi1");
                 read_attr!(index_meta, field, DeezGsi2, "gsi2");
                 read_attr!(index_meta, field, DeezGsi3, "gsi3");
@@ -103,19 +226,90 @@ pub fn derive(input: TokenStream) -> TokenStream {
                 read_attr!(index_meta, field, DeezGsi18, "gsi18");
                 read_attr!(index_meta, field, DeezGsi19, "gsi19");
                 read_attr!(index_meta, field, DeezGsi20, "gsi20");
+
+                for attr in field.attrs.iter().filter(|a| a.path().is_ident("deez_key")) {
+                    let key = DeezKey::from_attributes([attr])?;
+                    let encoding = key_encoding(field, attr, key.pad, key.sortable, key.time)?;
+                    add_composite(&mut index_meta, field, attr, &key.index, &key.key, key.position, encoding)?;
+                }
             }
         }
 
+        // local indexes share the primary hash key
+        let primary_hash_composite = index_meta["primary"].hash.composite.clone();
+        let primary_hash_template = index_meta["primary"].hash.template.clone();
+        let primary_hash_type = index_meta["primary"].hash.key_type.clone();
+        for v in index_meta.values_mut().filter(|v| v.local) {
+            if let Some(c) = v.hash.composite.first() {
+                return Err(syn::Error::new(c.span, "local secondary indexes share the primary hash key"));
+            }
+            v.hash.composite = primary_hash_composite.clone();
+            v.hash.template = primary_hash_template.clone();
+            v.hash.key_type = primary_hash_type.clone();
+        }
+
+        check_template_encodings(&index_meta, &struct_data.fields)?;
+
+        let hash_prefix = key_format.hash_prefix(&service, &entity);
+        let range_prefix = key_format.range_prefix(&entity);
+        let key_parts = format_ident!("{}KeyParts", ident);
+        let mut key_fields: Vec<&syn::Field> = Vec::new();
         for (k, v) in index_meta.iter() {
-            let service = s.service.clone();
-            let entity = s.entity.clone();
+            let index_key_fn_name = format_ident!("{}_key", k);
+            let index_keys_fn_name = format_ident!("{}_keys", k);
+            let index_parse_fn_name = format_ident!("parse_{}_key", k);
             let hash_field = v.hash.field.clone();
-            let range_field = v.range.field.clone();
+            let composed_hash = compose_key!(v.hash, rename_all, &struct_data.fields, &hash_prefix, &key_format);
 
-            let composed_hash = compose_key!(v.hash);
-            let composed_range = compose_key!(v.range);
+            // hash-only indexes have no range key to compose
+            let (range_arm, range_keys) = match &v.range {
+                Some(range) => {
+                    let range_field = range.field.clone();
+                    let composed_range = compose_key!(range, rename_all, &struct_data.fields, &range_prefix, &key_format);
+                    (
+                        quote! {
+                            index_key.field = #range_field.to_string();
+                            #composed_range
+                        },
+                        quote! { Some(self.#index_key_fn_name(Key::Range)) },
+                    )
+                }
+                // an empty, incomplete key
+                None => (quote! { index_key }, quote! { None }),
+            };
+
+            // keys are parsed after composing them, which checks the positions
+            let (hash_read, hash_fields) =
+                parse_key(&v.hash, rename_all, &struct_data.fields, &hash_prefix, &key_format, &ident.to_string())?;
+            key_fields.extend(hash_fields);
+            let (range_param, range_read) = match &v.range {
+                Some(range) => {
+                    let (range_read, range_fields) =
+                        parse_key(range, rename_all, &struct_data.fields, &range_prefix, &key_format, &ident.to_string())?;
+                    key_fields.extend(range_fields);
+                    (
+                        quote! { range: &str },
+                        quote! {
+                            let mut reader = KeyReader::new(range);
+                            #range_read
+                            reader.finish()?;
+                        },
+                    )
+                }
+                None => (quote! {}, quote! {}),
+            };
+            index_parse_fns = quote! {
+                #index_parse_fns
+                pub fn #index_parse_fn_name(hash: &str, #range_param) -> Result<#key_parts, DeezError> {
+                    let mut parts = #key_parts::default();
+                    let mut reader = KeyReader::new(hash);
+                    #hash_read
+                    reader.finish()?;
+                    #range_read
+                    Ok(parts)
+                }
+            };
 
-            let index_key_fn_name = format_ident!("{}_key", k);
             index_key_fns = quote! {
                 #index_key_fns
                 pub fn #index_key_fn_name(&self, key: Key) -> IndexKey {
@@ -126,46 +320,103 @@ pub fn derive(input: TokenStream) -> TokenStream {
                     match key {
                         Key::Hash => {
                             index_key.field = #hash_field.to_string();
-                            index_key.composite.push_str(&format!("${}#{}", #service, #entity));
                             #composed_hash
                         }
                         Key::Range => {
-                            index_key.field = #range_field.to_string();
-                            index_key.composite.push_str(&format!("${}", #entity));
-                            #composed_range
+                            #range_arm
                         }
                     }
                 }
             };
 
-            let index_keys_fn_name = format_ident!("{}_keys", k);
             index_keys_fns = quote! {
                 #index_keys_fns
                 pub fn #index_keys_fn_name(&self) -> IndexKeys {
                     IndexKeys {
                         hash: self.#index_key_fn_name(Key::Hash),
-                        range: self.#index_key_fn_name(Key::Range),
+                        range: #range_keys,
                     }
                 }
             };
 
-            index_inserts = quote! {
-                #index_inserts
-                {
-                    let keys = item.#index_keys_fn_name();
-                    // m.insert(keys.hash.field, AttributeValue::S(keys.hash.composite));
-                    m.insert(keys.hash.field(), keys.hash.av());
-                    m.insert(keys.range.field(), keys.range.av());
+            // the hash of a local index is the primary hash, already inserted
+            // the keys are moved into the item rather than cloned
+            attribute_count += match (v.local, &v.range) {
+                (true, _) | (false, None) => 1,
+                (false, Some(_)) => 2,
+            };
+            let hash_insert = key_insert(quote! { keys.hash }, &v.hash);
+            // hash-only indexes never have a range, but it is still matched
+            let range_insert = match &v.range {
+                Some(range) => key_insert(quote! { range }, range),
+                None => key_insert(quote! { range }, &IndexKey::default()),
+            };
+            index_inserts = if v.local && v.sparse {
+                quote! {
+                    #index_inserts
+                    {
+                        let range = item.#index_key_fn_name(Key::Range);
+                        if range.is_complete() {
+                            #range_insert
+                        }
+                    }
+                }
+            } else if v.local {
+                quote! {
+                    #index_inserts
+                    {
+                        let range = item.#index_key_fn_name(Key::Range);
+                        #range_insert
+                    }
                 }
+            } else if v.sparse {
+                quote! {
+                    #index_inserts
+                    {
+                        let keys = item.#index_keys_fn_name();
+                        if keys.hash.is_complete() {
+                            #hash_insert
+                            if let Some(range) = keys.range {
+                                #range_insert
+                            }
+                        }
+                    }
+                }
+            } else {
+                quote! {
+                    #index_inserts
+                    {
+                        let keys = item.#index_keys_fn_name();
+                        #hash_insert
+                        if let Some(range) = keys.range {
+                            #range_insert
+                        }
+                    }
+                }
+            };
+        }
+
+        let mut key_parts_fields = quote! {};
+        for field in struct_data.fields.iter().filter(|f| key_fields.iter().any(|k| k.ident == f.ident)) {
+            let field_ident = &field.ident;
+            let ty = key_part_type(field)?;
+            key_parts_fields = quote! {
+                #key_parts_fields
+                pub #field_ident: Option<#ty>,
             };
         }
 
-        let table = s.table;
         struct_impl = quote! {
+            #[derive(Default)]
+            #vis struct #key_parts {
+                #key_parts_fields
+            }
+
             impl #ident {
                 #index_name_fns
                 #index_key_fns
                 #index_keys_fns
+                #index_parse_fns
 
                 pub fn table_name() -> String {
                     #table.to_string()
@@ -176,247 +427,192 @@ pub fn derive(input: TokenStream) -> TokenStream {
                 }
             }
         };
+    } else {
+        for field in struct_data.fields.iter() {
+            if let Some(attr) = field.attrs.iter().find(|a| is_index_attr(a)) {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "index attributes require `table`, `service`, `entity` and primary keys in deez_schema",
+                ));
+            }
+        }
     }
 
     let mut field_inserts = quote! {};
+    let mut field_ref_inserts = quote! {};
     let mut field_reads = quote! {};
+    let mut uses_entity_default = false;
+    let entity = ident.to_string();
 
-    // todo: Null?
     for field in struct_data.fields.iter() {
-        if field.attrs.len() > 0 {
+        let field_ident = field.ident.as_ref().unwrap();
+        if !field.attrs.is_empty() {
             if let Ok(attribute) = DeezIgnore::from_attributes(&field.attrs) {
                 if attribute.ignore {
+                    // like the `..Default::default()` of the entity, if it has one
+                    let default = deez_default(field)?.unwrap_or_else(|| {
+                        uses_entity_default = true;
+                        quote! {
+                            match entity_default.as_mut() {
+                                Some(entity_default) => std::mem::take(&mut entity_default.#field_ident),
+                                None => Default::default(),
+                            }
+                        }
+                    });
+                    field_reads = quote! {
+                        #field_reads
+                        #field_ident: #default,
+                    };
                     continue;
                 }
             }
         }
 
-        let field_type = FieldType::new(&field.ty);
-        let field_ident = field.ident.as_ref().unwrap();
-        let field_name = field_ident.to_string();
-        let mut attribute_value_insert: proc_macro2::TokenStream;
-        let attribute_value_read: proc_macro2::TokenStream;
-
-        macro_rules! read_option {
-            ($variant:ident, $e:expr) => {{
-                let e = $e;
-                quote! {
-                    match &item[#field_name] {
-                        AttributeValue::$variant(inner) => Some(inner #e),
-                        AttributeValue::Null(_) => None,
-                        _ => panic!("unexpected AttributeValue variant"),
-                    }
-                }
-            }};
-        }
-
-        match field_type.inner_name.as_str() {
-            "String" => {
-                let list_insert = quote! {
-                    .iter()
-                    .map(|x| AttributeValue::S(x.clone()))
-                    .collect::<Vec<AttributeValue>>()
-                };
-                let list_read = quote! {
-                    .iter()
-                    .map(|x| x.as_s().unwrap().clone())
-                    .collect::<Vec<String>>()
-                };
+        let field_name = attribute_name(field, rename_all)?;
+        attribute_count += 1;
+        let (attribute_value_insert, attribute_value_ref_insert, attribute_value_read) = field_conversion(
+            field,
+            &entity,
+            &field_name,
+            quote! { item.#field_ident },
+            quote! { &item.#field_ident },
+        )?;
 
-                insert_read!(
-                    field_ident, field_type, field.attrs, attribute_value_insert, attribute_value_read;
-
-                    insert_list: quote! { AttributeValue::L(item.#field_ident #list_insert) },
-                    read_list: quote! { item[#field_name].as_l().unwrap() #list_read },
-                    insert_set: quote! { AttributeValue::Ss(item.#field_ident) },
-                    read_set: quote! { item[#field_name].as_ss().unwrap().clone() },
-                    insert_scalar: quote! { AttributeValue::S(item.#field_ident) },
-                    read_scalar: quote! { item[#field_name].as_s().unwrap().clone() },
-
-                    insert_option_list: quote! { AttributeValue::L(inner #list_insert) },
-                    read_option_list: read_option!(L, quote! { #list_read }),
-                    insert_option_set: quote! { AttributeValue::Ss(inner) },
-                    read_option_set: read_option!(Ss, quote! { .clone() }),
-                    insert_option_scalar: quote! { AttributeValue::S(inner) },
-                    read_option_scalar: read_option!(S, quote! { .clone() })
-                );
-            }
-
-            "f64" => {
-                let list_insert = quote! {
-                    .iter()
-                    .map(|x| AttributeValue::N(x.to_string()))
-                    .collect::<Vec<AttributeValue>>()
-                };
-                let set_insert = quote! {
-                    .iter()
-                    .map(|n| n.to_string())
-                    .collect::<Vec<String>>()
-                };
-                let list_read = quote! {
-                    .iter()
-                    .map(|x| x.as_n().unwrap().parse::<f64>().unwrap())
-                    .collect::<Vec<f64>>()
-                };
-                let set_read = quote! {
-                    .iter()
-                    .map(|x| x.parse::<f64>().unwrap())
-                    .collect::<Vec<f64>>()
-                };
-
-                insert_read!(
-                    field_ident, field_type, field.attrs, attribute_value_insert, attribute_value_read;
-
-                    insert_list: quote! { AttributeValue::L(item.#field_ident #list_insert) },
-                    read_list: quote! { item[#field_name].as_l().unwrap() #list_read },
-                    insert_set: quote! { AttributeValue::Ns(item.#field_ident #set_insert) },
-                    read_set: quote! { item[#field_name].as_ns().unwrap() #set_read },
-                    insert_scalar: quote! { AttributeValue::N(item.#field_ident.to_string()) },
-                    read_scalar: quote! { item[#field_name].as_n().unwrap().parse::<f64>().unwrap() },
-
-                    insert_option_list: quote! { AttributeValue::L(inner #list_insert) },
-                    read_option_list: read_option!(L, quote! { #list_read }),
-                    insert_option_set: quote! { AttributeValue::Ns(inner #set_insert) },
-                    read_option_set: read_option!(Ns, quote! { #set_read }),
-                    insert_option_scalar: quote! { AttributeValue::N(inner.to_string()) },
-                    read_option_scalar: read_option!(N, quote! { .clone().parse::<f64>().unwrap() })
-                );
-            }
-
-            "bool" => {
-                let list_insert = quote! {
-                    .iter()
-                    .map(|x| AttributeValue::Bool(x.clone()))
-                    .collect::<Vec<AttributeValue>>()
-                };
-                let list_read = quote! {
-                    .iter()
-                    .map(|x| x.as_bool().unwrap().clone())
-                    .collect::<Vec<bool>>()
-                };
-
-                insert_read!(
-                    field_ident, field_type, field.attrs, attribute_value_insert, attribute_value_read;
-
-                    insert_list: quote! { AttributeValue::L(item.#field_ident #list_insert) },
-                    read_list: quote! { item[#field_name].as_l().unwrap() #list_read },
-                    insert_set: panic!("invalid type for `set`: {}", field_type.inner_name),
-                    read_set: panic!("invalid type for `set`: {}", field_type.inner_name),
-                    insert_scalar: quote! { AttributeValue::Bool(item.#field_ident) },
-                    read_scalar: quote! { item[#field_name].as_bool().unwrap().clone() },
-
-                    insert_option_list: quote! { AttributeValue::L(inner #list_insert) },
-                    read_option_list: read_option!(L, quote! { #list_read }),
-                    insert_option_set: panic!("invalid type for `set`: {}", field_type.inner_name),
-                    read_option_set: panic!("invalid type for `set`: {}", field_type.inner_name),
-                    insert_option_scalar: quote! { AttributeValue::Bool(inner) },
-                    read_option_scalar: read_option!(Bool, quote! { .clone() })
-                );
-            }
-
-            // todo: better pattern matching for "Blob"
-            "Blob" => {
-                let list_insert = quote! {
-                    .iter()
-                    .map(|x| AttributeValue::B(x.clone()))
-                    .collect::<Vec<AttributeValue>>()
-                };
-                let list_read = quote! {
-                    .iter()
-                    .map(|x| x.as_b().unwrap().clone())
-                    .collect::<Vec<Blob>>()
-                };
-
-                insert_read!(
-                    field_ident, field_type, field.attrs, attribute_value_insert, attribute_value_read;
-
-                    insert_list: quote! { AttributeValue::L(item.#field_ident #list_insert) },
-                    read_list: quote! { item[#field_name].as_l().unwrap() #list_read },
-                    insert_set: quote! { AttributeValue::Bs(item.#field_ident) },
-                    read_set: quote! { item[#field_name].as_bs().unwrap().clone() },
-                    insert_scalar: quote! { AttributeValue::B(item.#field_ident) },
-                    read_scalar: quote! { item[#field_name].as_b().unwrap().clone() },
-
-                    insert_option_list: quote! { AttributeValue::L(inner #list_insert) },
-                    read_option_list: read_option!(L, quote! { #list_read }),
-                    insert_option_set: quote! { AttributeValue::Bs(inner) },
-                    read_option_set: read_option!(Bs, quote! { .clone() }),
-                    insert_option_scalar: quote! { AttributeValue::B(inner) },
-                    read_option_scalar: read_option!(B, quote! { .clone() })
-                );
+        let skip_none = if DeezField::from_attributes(&field.attrs)?.skip_none {
+            if !is_option(&field.ty) {
+                return Err(syn::Error::new_spanned(
+                    &field.ty,
+                    "`skip_none` can only be used with `Option` fields",
+                ));
             }
+            true
+        } else {
+            s.skip_none && is_option(&field.ty)
+        };
 
-            _ => {
-                let field_type_ident = format_ident!("{}", field_type.inner_name);
-                let list_insert = quote! {
-                    .into_iter()
-                    .map(|x| AttributeValue::M(x.into()))
-                    .collect::<Vec<AttributeValue>>()
-                };
-                let list_read = quote! {
-                    .iter()
-                    .map(|x| x.as_m().unwrap().into())
-                    .collect::<Vec<#field_type_ident>>()
-                };
+        let insert = empty_insert(field, &field_name, attribute_value_insert)?;
+        let ref_insert = empty_insert(field, &field_name, attribute_value_ref_insert)?;
+        if skip_none {
+            field_inserts = quote! {
+                #field_inserts
+                if item.#field_ident.is_some() {
+                    #insert
+                }
+            };
 
-                insert_read!(
-                    field_ident, field_type, field.attrs, attribute_value_insert, attribute_value_read;
-
-                    insert_list: quote! { AttributeValue::L(item.#field_ident #list_insert) },
-                    read_list: quote! { item[#field_name].as_l().unwrap() #list_read },
-                    insert_set: panic!("invalid type for `set`: {}", field_type.inner_name),
-                    read_set: panic!("invalid type for `set`: {}", field_type.inner_name),
-                    insert_scalar: quote! { AttributeValue::M(item.#field_ident.into()) },
-                    read_scalar: quote! { item[#field_name].as_m().unwrap().into() },
-
-                    insert_option_list: quote! { AttributeValue::L(inner #list_insert) },
-                    read_option_list: quote! {
-                        match &item[#field_name] {
-                            AttributeValue::L(inner) => Some(inner #list_read),
-                            AttributeValue::Null(_) => None,
-                            _ => panic!("unexpected AttributeValue variant"),
-                        }
-                    },
-                    insert_option_set: panic!("invalid type for `set`: {}", field_type.inner_name),
-                    read_option_set: panic!("invalid type for `set`: {}", field_type.inner_name),
-                    insert_option_scalar: quote! { AttributeValue::M(inner.into()) },
-                    read_option_scalar: read_option!(M, quote! { .into() })
-                );
-            }
-        }
+            field_ref_inserts = quote! {
+                #field_ref_inserts
+                if item.#field_ident.is_some() {
+                    #ref_insert
+                }
+            };
+        } else {
+            field_inserts = quote! {
+                #field_inserts
+                #insert
+            };
 
-        field_inserts = quote! {
-            #field_inserts
-            m.insert(#field_name.to_string(), #attribute_value_insert);
-        };
+            field_ref_inserts = quote! {
+                #field_ref_inserts
+                #ref_insert
+            };
+        }
 
         field_reads = quote! {
             #field_reads
-            i.#field_ident = #attribute_value_read;
+            #field_ident: #attribute_value_read,
         };
     }
 
+    let entity_default = if uses_entity_default {
+        quote! {
+            let mut entity_default = (&EntityDefault::<#ident>(std::marker::PhantomData)).entity_default();
+        }
+    } else {
+        quote! {}
+    };
     let response_items = format_ident!("{}Items", ident);
+    let unexpected = unexpected_variant!(String::new(), String::new(), "M");
 
     let out = quote! {
         #struct_impl
 
+        impl #ident {
+            pub fn to_item(&self) -> HashMap<String, AttributeValue> {
+                let item = self;
+                let mut m: HashMap<String, AttributeValue> = HashMap::with_capacity(#attribute_count);
+                #index_inserts
+                #field_ref_inserts
+                m
+            }
+
+            /// Like `to_item`, but returns an error if a number is not valid in
+            /// DynamoDB, e.g. an `i128` or `u128` of more than 38 digits.
+            pub fn try_to_item(&self) -> Result<HashMap<String, AttributeValue>, DeezError> {
+                let m = self.to_item();
+                for (field, av) in &m {
+                    check_numbers(av).map_err(|e| e.in_field(#entity, field))?;
+                }
+                Ok(m)
+            }
+        }
+
         impl From<#ident> for HashMap<String, AttributeValue> {
             fn from(item: #ident) -> HashMap<String, AttributeValue> {
-                let mut m: HashMap<String, AttributeValue> = HashMap::new();
+                let mut m: HashMap<String, AttributeValue> = HashMap::with_capacity(#attribute_count);
                 #index_inserts
                 #field_inserts
                 m
             }
         }
 
-        impl From<&HashMap<String, AttributeValue>> for #ident {
-            fn from(item: &HashMap<String, AttributeValue>) -> #ident {
-                let mut i = #ident {
-                    ..Default::default()
-                };
-                #field_reads
-                i
+        impl From<&#ident> for HashMap<String, AttributeValue> {
+            fn from(item: &#ident) -> HashMap<String, AttributeValue> {
+                item.to_item()
+            }
+        }
+
+        impl TryFrom<&HashMap<String, AttributeValue>> for #ident {
+            type Error = DeezError;
+
+            fn try_from(item: &HashMap<String, AttributeValue>) -> Result<#ident, DeezError> {
+                #entity_default
+                Ok(#ident {
+                    #field_reads
+                })
+            }
+        }
+
+        impl IntoAttributeValue for #ident {
+            fn to_av(&self) -> AttributeValue {
+                AttributeValue::M(self.to_item())
+            }
+
+            fn into_av(self) -> AttributeValue {
+                AttributeValue::M(self.into())
+            }
+        }
+
+        impl FromAttributeValue for #ident {
+            fn from_av(av: &AttributeValue) -> Result<#ident, DeezError> {
+                match av {
+                    AttributeValue::M(m) => #ident::try_from(m),
+                    _ => Err(#unexpected),
+                }
+            }
+        }
+
+        impl From<#ident> for AttributeValue {
+            fn from(item: #ident) -> AttributeValue {
+                item.into_av()
+            }
+        }
+
+        impl TryFrom<&AttributeValue> for #ident {
+            type Error = DeezError;
+
+            fn try_from(av: &AttributeValue) -> Result<#ident, DeezError> {
+                FromAttributeValue::from_av(av)
             }
         }
 
@@ -429,13 +625,18 @@ pub fn derive(input: TokenStream) -> TokenStream {
             }
         }
 
-        impl From<&[HashMap<String, AttributeValue>]> for #response_items {
-            fn from(item: &[HashMap<String, AttributeValue>]) -> #response_items {
-                let mut items: Vec<#ident> = Vec::new();
-                for i in item {
-                    items.push(i.into());
+        impl TryFrom<&[HashMap<String, AttributeValue>]> for #response_items {
+            type Error = DeezError;
+
+            fn try_from(item: &[HashMap<String, AttributeValue>]) -> Result<#response_items, DeezError> {
+                let mut items: Vec<#ident> = Vec::with_capacity(item.len());
+                for (index, i) in item.iter().enumerate() {
+                    items.push(i.try_into().map_err(|e| DeezError::Item {
+                        index,
+                        source: Box::new(e),
+                    })?);
                 }
-                #response_items(items)
+                Ok(#response_items(items))
             }
         }
 
@@ -466,5 +667,1652 @@ pub fn derive(input: TokenStream) -> TokenStream {
         // }
     };
 
-    out.into()
+    Ok(out)
+}
+
+fn derive_enum(ident: syn::Ident, attrs: Vec<syn::Attribute>, data: syn::DataEnum) -> syn::Result<proc_macro2::TokenStream> {
+    let tag = DeezEnum::from_attributes(&attrs)?.tag;
+    let enum_name = ident.to_string();
+    let unknown = quote! {
+        DeezError::UnknownVariant {
+            entity: String::new(),
+            field: String::new(),
+            value: name.clone(),
+        }
+    };
+
+    let mut names = quote! {};
+    let mut inserts = quote! {};
+    let mut unit_reads = quote! {};
+    let mut data_reads = quote! {};
+
+    for variant in data.variants.iter() {
+        let variant_ident = &variant.ident;
+        let name = match DeezVariant::from_attributes(&variant.attrs)?.rename {
+            Some(rename) => rename,
+            None => variant_ident.to_string(),
+        };
+        let entity = format!("{}::{}", enum_name, variant_ident);
+        // the variant name alone would give different values the same key
+        let data_key_part = format!("`{}` carries data and can not be used in a composite key", entity);
+
+        match &variant.fields {
+            syn::Fields::Unit => {
+                names = quote! {
+                    #names
+                    #ident::#variant_ident => #name,
+                };
+                inserts = match &tag {
+                    None => quote! {
+                        #inserts
+                        #ident::#variant_ident => AttributeValue::S(#name.to_string()),
+                    },
+                    Some(tag) => quote! {
+                        #inserts
+                        #ident::#variant_ident => AttributeValue::M(HashMap::from([(
+                            #tag.to_string(),
+                            AttributeValue::S(#name.to_string()),
+                        )])),
+                    },
+                };
+                unit_reads = quote! {
+                    #unit_reads
+                    #name => Ok(#ident::#variant_ident),
+                };
+            }
+
+            syn::Fields::Named(fields) => {
+                let mut field_idents = Vec::new();
+                let mut field_inserts = quote! {};
+                let mut field_reads = quote! {};
+
+                for field in fields.named.iter() {
+                    let field_ident = field.ident.as_ref().unwrap();
+                    let field_name = attribute_name(field, None)?;
+                    let value = quote! { #field_ident };
+                    let (_, attribute_value_insert, attribute_value_read) =
+                        field_conversion(field, &entity, &field_name, value.clone(), value)?;
+
+                    field_idents.push(field_ident);
+                    field_inserts = quote! {
+                        #field_inserts
+                        m.insert(#field_name.to_string(), #attribute_value_insert);
+                    };
+                    field_reads = quote! {
+                        #field_reads
+                        #field_ident: #attribute_value_read,
+                    };
+                }
+
+                names = quote! {
+                    #names
+                    #ident::#variant_ident { .. } => panic!(#data_key_part),
+                };
+                inserts = match &tag {
+                    None => quote! {
+                        #inserts
+                        #ident::#variant_ident { #(#field_idents),* } => {
+                            let mut m: HashMap<String, AttributeValue> = HashMap::new();
+                            #field_inserts
+                            AttributeValue::M(HashMap::from([(#name.to_string(), AttributeValue::M(m))]))
+                        }
+                    },
+                    Some(tag) => quote! {
+                        #inserts
+                        #ident::#variant_ident { #(#field_idents),* } => {
+                            let mut m: HashMap<String, AttributeValue> = HashMap::new();
+                            m.insert(#tag.to_string(), AttributeValue::S(#name.to_string()));
+                            #field_inserts
+                            AttributeValue::M(m)
+                        }
+                    },
+                };
+                data_reads = match &tag {
+                    None => {
+                        let unexpected = unexpected_variant!(entity, name, "M");
+                        quote! {
+                            #data_reads
+                            #name => {
+                                let item = match &item[#name] {
+                                    AttributeValue::M(item) => item,
+                                    _ => return Err(#unexpected),
+                                };
+                                Ok(#ident::#variant_ident { #field_reads })
+                            }
+                        }
+                    }
+                    Some(_) => quote! {
+                        #data_reads
+                        #name => Ok(#ident::#variant_ident { #field_reads }),
+                    },
+                };
+            }
+
+            syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let field = &fields.unnamed[0];
+
+                names = quote! {
+                    #names
+                    #ident::#variant_ident(..) => panic!(#data_key_part),
+                };
+
+                match &tag {
+                    // stored like a struct field named after the variant
+                    None => {
+                        let value = quote! { inner };
+                        let (_, attribute_value_insert, attribute_value_read) =
+                            field_conversion(field, &enum_name, &name, value.clone(), value)?;
+
+                        inserts = quote! {
+                            #inserts
+                            #ident::#variant_ident(inner) => {
+                                AttributeValue::M(HashMap::from([(#name.to_string(), #attribute_value_insert)]))
+                            }
+                        };
+                        data_reads = quote! {
+                            #data_reads
+                            #name => Ok(#ident::#variant_ident(#attribute_value_read)),
+                        };
+                    }
+                    // the tag is added to the map of the inner struct
+                    Some(tag) => {
+                        if !is_struct_type(&field.ty)? {
+                            return Err(syn::Error::new_spanned(
+                                variant,
+                                "tuple variants of a tagged enum must wrap a struct deriving `Deez`",
+                            ));
+                        }
+                        // trait errors point at the wrapped type
+                        let ty = &field.ty;
+                        let span = ty.span();
+                        let to_item = quote_spanned! {span=> <#ty>::to_item(inner) };
+                        let try_from = quote_spanned! {span=> <#ty>::try_from(item) };
+                        inserts = quote! {
+                            #inserts
+                            #ident::#variant_ident(inner) => {
+                                let mut m = #to_item;
+                                m.insert(#tag.to_string(), AttributeValue::S(#name.to_string()));
+                                AttributeValue::M(m)
+                            }
+                        };
+                        data_reads = quote! {
+                            #data_reads
+                            #name => Ok(#ident::#variant_ident(#try_from?)),
+                        };
+                    }
+                }
+            }
+
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    variant,
+                    "tuple variants must have exactly one field",
+                ))
+            }
+        }
+    }
+
+    let read = match &tag {
+        None => {
+            let unexpected = unexpected_variant!(String::new(), String::new(), "S");
+            let data_arm = if data_reads.is_empty() {
+                quote! {}
+            } else {
+                quote! {
+                    AttributeValue::M(item) if item.len() == 1 => {
+                        let name = item.keys().next().unwrap();
+                        match name.as_str() {
+                            #data_reads
+                            _ => Err(#unknown),
+                        }
+                    }
+                }
+            };
+            quote! {
+                match av {
+                    AttributeValue::S(name) => match name.as_str() {
+                        #unit_reads
+                        _ => Err(#unknown),
+                    },
+                    #data_arm
+                    _ => Err(#unexpected),
+                }
+            }
+        }
+        Some(tag) => {
+            let unexpected = unexpected_variant!(String::new(), String::new(), "M");
+            let unexpected_tag = unexpected_variant!(enum_name, tag, "S");
+            quote! {
+                match av {
+                    AttributeValue::M(item) => {
+                        let name = match item.get(#tag) {
+                            Some(AttributeValue::S(name)) => name,
+                            Some(_) => return Err(#unexpected_tag),
+                            None => {
+                                return Err(DeezError::MissingAttribute {
+                                    entity: #enum_name.to_string(),
+                                    field: #tag.to_string(),
+                                })
+                            }
+                        };
+                        match name.as_str() {
+                            #unit_reads
+                            #data_reads
+                            _ => Err(#unknown),
+                        }
+                    }
+                    _ => Err(#unexpected),
+                }
+            }
+        }
+    };
+
+    let out = quote! {
+        impl IntoAttributeValue for #ident {
+            fn to_av(&self) -> AttributeValue {
+                match self {
+                    #inserts
+                }
+            }
+
+            // stored name of unit variants
+            fn to_key_part(&self) -> String {
+                match self {
+                    #names
+                }
+                .to_string()
+            }
+        }
+
+        impl FromAttributeValue for #ident {
+            fn from_av(av: &AttributeValue) -> Result<#ident, DeezError> {
+                #read
+            }
+        }
+
+        impl From<#ident> for AttributeValue {
+            fn from(item: #ident) -> AttributeValue {
+                item.to_av()
+            }
+        }
+
+        impl TryFrom<&AttributeValue> for #ident {
+            type Error = DeezError;
+
+            fn try_from(av: &AttributeValue) -> Result<#ident, DeezError> {
+                FromAttributeValue::from_av(av)
+            }
+        }
+    };
+
+    Ok(out)
+}
+
+/// Whether `ty` may be a struct deriving `Deez`, i.e. it is not an `Option`,
+/// a collection or one of the other supported field types.
+fn is_struct_type(ty: &syn::Type) -> syn::Result<bool> {
+    let field_type = FieldType::new(ty)?;
+    if field_type.is_option || field_type.is_vec || field_type.is_set || field_type.is_map {
+        return Ok(false);
+    }
+    let name = field_type.inner_name.split('<').next().unwrap_or_default();
+    let name = name.rsplit("::").next().unwrap_or_default().trim();
+    Ok(!matches!(
+        name,
+        "String" | "str" | "bool" | "char" | "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64"
+            | "i128" | "isize" | "f32" | "f64" | "Number" | "Decimal" | "Blob" | "SystemTime" | "DateTime" | "NaiveDate"
+            | "AttributeValue" | "Box"
+    ))
+}
+
+/// Name of the attribute a field is stored under.
+fn attribute_name(field: &syn::Field, rename_all: Option<&str>) -> syn::Result<String> {
+    if let Some(rename) = DeezField::from_attributes(&field.attrs)?.rename {
+        return Ok(rename);
+    }
+    let name = field.ident.as_ref().unwrap().to_string();
+    let name = name.strip_prefix("r#").unwrap_or(&name);
+    Ok(match rename_all {
+        // the rule is checked before any field is converted
+        Some(case) => convert_case(name, case).unwrap_or_default(),
+        None => name.to_string(),
+    })
+}
+
+/// Converts a snake_case field name, using the same rules as serde's `rename_all`.
+fn convert_case(name: &str, case: &str) -> Option<String> {
+    let words: Vec<&str> = name.split('_').filter(|w| !w.is_empty()).collect();
+    let capitalize = |w: &str| {
+        let mut c = w.chars();
+        match c.next() {
+            Some(f) => f.to_uppercase().chain(c).collect::<String>(),
+            None => String::new(),
+        }
+    };
+    Some(match case {
+        "lowercase" => words.concat().to_lowercase(),
+        "UPPERCASE" => words.concat().to_uppercase(),
+        "PascalCase" => words.iter().map(|w| capitalize(w)).collect(),
+        "camelCase" => words
+            .iter()
+            .enumerate()
+            .map(|(i, w)| if i == 0 { w.to_string() } else { capitalize(w) })
+            .collect(),
+        "snake_case" => words.join("_"),
+        "SCREAMING_SNAKE_CASE" => words.join("_").to_uppercase(),
+        "kebab-case" => words.join("-"),
+        "SCREAMING-KEBAB-CASE" => words.join("-").to_uppercase(),
+        _ => return None,
+    })
+}
+
+/// Span of the `deez_schema` attribute setting `key`, or of the first one.
+fn schema_span(attrs: &[syn::Attribute], key: &str) -> proc_macro2::Span {
+    let schemas = || attrs.iter().filter(|a| a.path().is_ident("deez_schema"));
+    schemas()
+        .find(|a| match &a.meta {
+            syn::Meta::List(l) => l
+                .tokens
+                .clone()
+                .into_iter()
+                .any(|t| matches!(t, proc_macro2::TokenTree::Ident(i) if i == key)),
+            _ => false,
+        })
+        .or_else(|| schemas().next())
+        .map(|a| a.span())
+        .unwrap_or_else(proc_macro2::Span::call_site)
+}
+
+fn is_index_attr(attr: &syn::Attribute) -> bool {
+    match attr.path().get_ident() {
+        Some(i) => {
+            let i = i.to_string();
+            i == "deez_primary" || i == "deez_key" || i.starts_with("deez_gsi")
+        }
+        None => false,
+    }
+}
+
+/// Reads the `key_*` options of the schema.
+fn key_format(s: &DeezSchema, attrs: &[syn::Attribute]) -> syn::Result<KeyFormat> {
+    let delimiter = s.key_delimiter.clone().unwrap_or_else(|| "#".to_string());
+    if delimiter.is_empty() {
+        return Err(syn::Error::new(
+            schema_span(attrs, "key_delimiter"),
+            "`key_delimiter` can not be empty",
+        ));
+    }
+    let casing = match s.key_casing.as_deref() {
+        None | Some("none") => None,
+        Some(casing @ ("upper" | "lower")) => Some(casing.to_string()),
+        Some(_) => {
+            return Err(syn::Error::new(
+                schema_span(attrs, "key_casing"),
+                "`key_casing` must be one of `upper`, `lower` or `none`",
+            ))
+        }
+    };
+    Ok(KeyFormat {
+        delimiter,
+        label_separator: s.key_label_separator.clone().unwrap_or_else(|| "_".to_string()),
+        prefix: s.key_prefix.unwrap_or(true),
+        casing,
+    })
+}
+
+/// Sets the key templates of an index declared with `hash_template` or
+/// `range_template`.
+fn set_templates(
+    index_meta: &mut HashMap<String, IndexKeys>,
+    index: &str,
+    hash: Option<(String, proc_macro2::Span)>,
+    range: Option<(String, proc_macro2::Span)>,
+) -> syn::Result<()> {
+    let index_keys = index_meta.get_mut(index).unwrap();
+    index_keys.hash.template = hash;
+    if let Some((template, span)) = range {
+        match index_keys.range.as_mut() {
+            Some(range) => range.template = Some((template, span)),
+            None => return Err(syn::Error::new(span, format!("index `{}` has no range key", index))),
+        }
+    }
+    Ok(())
+}
+
+/// Generates the code moving `key`, an `IndexKey`, into the item `m`. Number
+/// and binary keys are only inserted when complete, as they can not be empty.
+fn key_insert(key: proc_macro2::TokenStream, index_key: &IndexKey) -> proc_macro2::TokenStream {
+    match index_key.key_type {
+        Some(_) => quote! {
+            if #key.is_complete() {
+                m.insert(#key.field(), #key.av());
+            }
+        },
+        None => quote! {
+            m.insert(#key.field, AttributeValue::S(#key.composite));
+        },
+    }
+}
+
+/// Checks that the field of a number key is a number or an epoch timestamp,
+/// and that the field of a binary key is a `Blob`.
+fn check_key_type(key_type: &str, composite: &Composite) -> syn::Result<()> {
+    let field_type = FieldType::new(&composite.syn_field.ty)?;
+    let collection = field_type.is_vec || field_type.is_set || field_type.is_map;
+    let ty = field_type.inner_name.rsplit("::").next().unwrap_or_default().trim();
+    let time = match &composite.encoding.time {
+        Some(time) => Some(time.clone()),
+        None => DeezTime::from_attributes(&composite.syn_field.attrs)?.format,
+    };
+    let numeric = matches!(
+        ty,
+        "u8" | "u16" | "u32" | "u64" | "u128" | "usize" | "i8" | "i16" | "i32" | "i64" | "i128" | "isize" | "f64"
+            | "Number" | "Decimal"
+    );
+    let error = match key_type {
+        "number" if time.as_deref() == Some("iso8601") => "`iso8601` timestamps can not be used in number keys",
+        "number" if time.is_none() && (collection || !numeric) => {
+            "number keys require an integer, `f64`, `Number` or `Decimal` field, or an epoch timestamp"
+        }
+        "binary" if time.is_some() || collection || ty != "Blob" => "binary keys require a `Blob` field",
+        _ => return Ok(()),
+    };
+    Err(syn::Error::new(composite.span, error))
+}
+
+/// Sets the attribute types of an index's keys declared with `hash_type` or
+/// `range_type`. String keys are the default.
+fn set_key_types(
+    index_meta: &mut HashMap<String, IndexKeys>,
+    index: &str,
+    hash: Option<(String, proc_macro2::Span)>,
+    range: Option<(String, proc_macro2::Span)>,
+) -> syn::Result<()> {
+    let index_keys = index_meta.get_mut(index).unwrap();
+    for (key, key_type) in [("hash", hash), ("range", range)] {
+        let (key_type, span) = match key_type {
+            Some(key_type) => key_type,
+            None => continue,
+        };
+        if !matches!(key_type.as_str(), "string" | "number" | "binary") {
+            return Err(syn::Error::new(span, "key type must be one of `string`, `number` or `binary`"));
+        }
+        let index_key = match (key, index_keys.range.as_mut()) {
+            ("hash", _) => &mut index_keys.hash,
+            (_, Some(range)) => range,
+            (_, None) => return Err(syn::Error::new(span, format!("index `{}` has no range key", index))),
+        };
+        if key_type != "string" {
+            index_key.key_type = Some((key_type, span));
+        }
+    }
+    Ok(())
+}
+
+/// Splits a template like `ORG#${org_id}#USER#${user_id}` into the text before
+/// each placeholder with the placeholder's field, and the trailing text.
+fn template_parts<'a>(
+    template: &'a str,
+    span: proc_macro2::Span,
+    fields: &'a syn::Fields,
+) -> syn::Result<(Vec<(&'a str, &'a syn::Field)>, &'a str)> {
+    // `\` escapes values, and could be mistaken for the text after one
+    if template.contains('\\') {
+        return Err(syn::Error::new(span, format!("template `{}` can not contain `\\`", template)));
+    }
+    let mut parts = Vec::new();
+    let mut rest = template;
+    while let Some(start) = rest.find("${") {
+        let literal = &rest[..start];
+        // a key can only be parsed if the values are separated
+        if literal.is_empty() && !parts.is_empty() {
+            return Err(syn::Error::new(
+                span,
+                format!("placeholders in template `{}` must be separated by text", template),
+            ));
+        }
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => {
+                return Err(syn::Error::new(
+                    span,
+                    format!("unclosed placeholder in template `{}`", template),
+                ))
+            }
+        };
+        let name = &rest[start + 2..end];
+        let field = match fields.iter().find(|f| f.ident.as_ref().is_some_and(|i| i == name)) {
+            Some(field) => field,
+            None => {
+                return Err(syn::Error::new(
+                    span,
+                    format!("`{}` in template `{}` is not a field", name, template),
+                ))
+            }
+        };
+        parts.push((literal, field));
+        rest = &rest[end + 1..];
+    }
+    Ok((parts, rest))
+}
+
+/// Rejects template placeholders for fields declared with `pad`, `sortable` or
+/// `time`, which only apply to the composite they are declared on.
+fn check_template_encodings(index_meta: &HashMap<String, IndexKeys>, fields: &syn::Fields) -> syn::Result<()> {
+    let keys = || index_meta.values().flat_map(|v| std::iter::once(&v.hash).chain(&v.range));
+    let encoded: Vec<&Composite> = keys()
+        .flat_map(|k| &k.composite)
+        .filter(|c| c.encoding.pad.is_some() || c.encoding.sortable || c.encoding.time.is_some())
+        .collect();
+    for (template, span) in keys().filter_map(|k| k.template.as_ref()) {
+        let (parts, _) = template_parts(template, *span, fields)?;
+        for (_, field) in parts {
+            if encoded.iter().any(|c| c.syn_field.ident == field.ident) {
+                return Err(syn::Error::new(
+                    *span,
+                    format!(
+                        "`{}` has a key encoding and can not be used in the template `{}`",
+                        field.ident.as_ref().unwrap(),
+                        template
+                    ),
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Generates the code composing a key from a template. Like the default
+/// format, the key ends at the first placeholder whose field is `None`, e.g.
+/// `ORG#acme#USER#`.
+fn compose_template(
+    template: &str,
+    span: proc_macro2::Span,
+    fields: &syn::Fields,
+    key_format: &KeyFormat,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let mut c = quote! {};
+    let (parts, rest) = template_parts(template, span, fields)?;
+    for (i, (literal, field)) in parts.iter().enumerate() {
+        if !literal.is_empty() {
+            c = quote! {
+                #c
+                index_key.composite.push_str(#literal);
+            };
+        }
+        // encoded fields are rejected by `check_template_encodings`
+        let end = parts.get(i + 1).map(|(literal, _)| *literal).unwrap_or(rest);
+        let push = push_key_part(field, "", Some(key_format), end, &KeyEncoding::default())?;
+        c = quote! {
+            #c
+            #push
+        };
+    }
+    if !rest.is_empty() {
+        c = quote! {
+            #c
+            index_key.composite.push_str(#rest);
+        };
+    }
+    Ok(c)
+}
+
+/// Generates the code reading a key composed by `compose_key!` with `reader`
+/// into the fields of `parts`, returning the fields it reads. Reading stops at
+/// the end of a partial key, leaving the remaining fields `None`.
+fn parse_key<'a>(
+    index_key: &'a IndexKey,
+    rename_all: Option<&str>,
+    fields: &'a syn::Fields,
+    prefix: &str,
+    key_format: &KeyFormat,
+    entity: &str,
+) -> syn::Result<(proc_macro2::TokenStream, Vec<&'a syn::Field>)> {
+    let mut c;
+    let mut key_fields = Vec::new();
+    if index_key.key_type.is_some() {
+        // the whole key is the value, checked to be a single field by `compose_key!`
+        let composite = &index_key.composite[0];
+        let read = read_key_part(&composite.syn_field, rename_all, entity, "", &composite.encoding, false)?;
+        c = quote! {
+            if !reader.is_empty() {
+                #read
+            }
+        };
+        key_fields.push(&composite.syn_field);
+    } else if let Some((template, span)) = &index_key.template {
+        let (parts, rest) = template_parts(template, *span, fields)?;
+        let first = parts.first().map(|(literal, _)| *literal).unwrap_or(rest);
+        c = quote! { reader.literal(#first)?; };
+        for (i, (_, field)) in parts.iter().enumerate() {
+            // each value ends at the text following its placeholder
+            let end = parts.get(i + 1).map(|(literal, _)| *literal).unwrap_or(rest);
+            let read = read_key_part(field, rename_all, entity, end, &KeyEncoding::default(), true)?;
+            c = quote! {
+                #c
+                if !reader.is_empty() {
+                    #read
+                    reader.literal(#end)?;
+                }
+            };
+            key_fields.push(*field);
+        }
+    } else {
+        c = quote! { reader.literal(#prefix)?; };
+        let mut composite = index_key.composite.iter().collect::<Vec<_>>();
+        composite.sort_by_key(|c| c.position);
+        for (i, composite) in composite.into_iter().enumerate() {
+            let field_name = attribute_name(&composite.syn_field, rename_all)?;
+            let label = key_format.label(&field_name, i == 0);
+            let read = read_key_part(
+                &composite.syn_field,
+                rename_all,
+                entity,
+                &key_format.delimiter,
+                &composite.encoding,
+                true,
+            )?;
+            c = quote! {
+                #c
+                if !reader.is_empty() {
+                    reader.literal(#label)?;
+                    #read
+                }
+            };
+            key_fields.push(&composite.syn_field);
+        }
+    }
+    Ok((c, key_fields))
+}
+
+/// Generates the code reading the value of `field` up to `end` and setting it
+/// in `parts`.
+fn read_key_part(
+    field: &syn::Field,
+    rename_all: Option<&str>,
+    entity: &str,
+    end: &str,
+    encoding: &KeyEncoding,
+    padded: bool,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let field_ident = field.ident.as_ref().unwrap();
+    let field_name = attribute_name(field, rename_all)?;
+    let ty = key_part_type(field)?;
+    let from_key_part = from_key_part!(field, ty, entity, field_name, encoding, padded);
+    Ok(quote! {
+        let value = reader.value(#end);
+        parts.#field_ident = Some(#from_key_part?);
+    })
+}
+
+/// Type of a field's value in a key, i.e. `T` for `Option<T>`.
+fn key_part_type(field: &syn::Field) -> syn::Result<syn::Type> {
+    let field_type = FieldType::new(&field.ty)?;
+    match field_type.is_option {
+        true => syn::parse_str(&field_type.inner_name),
+        false => Ok(field.ty.clone()),
+    }
+}
+
+/// Generates the code appending `label` and the key part of `field` to a key,
+/// returning the partial key if an `Option` field is `None`. Without a
+/// `key_format`, the value of a number or binary key is neither escaped nor
+/// cased.
+///
+/// The value is escaped against the delimiter and the first character of
+/// `end`, the template text following it, so that `KeyReader::value` can not
+/// stop inside the value.
+fn push_key_part(
+    field: &syn::Field,
+    label: &str,
+    key_format: Option<&KeyFormat>,
+    end: &str,
+    encoding: &KeyEncoding,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let field_ident = field.ident.as_ref().unwrap();
+    let key_part = match key_format {
+        Some(key_format) => {
+            let escaped = format!("{}{}", key_format.delimiter, end.chars().next().map(String::from).unwrap_or_default());
+            let key_part = key_format.case_value(key_part!(field, encoding, true));
+            quote! { escape_key_part(&#key_part, #escaped) }
+        }
+        None => key_part!(field, encoding, false),
+    };
+    let push_label = if label.is_empty() {
+        quote! {}
+    } else {
+        quote! { index_key.composite.push_str(#label); }
+    };
+    // only `Option` fields can be missing, ending a partial key
+    Ok(if is_option(&field.ty) {
+        quote! {
+            if let Some(inner) = &self.#field_ident {
+                #push_label
+                index_key.composite.push_str(&#key_part);
+            } else {
+                return index_key;
+            }
+        }
+    } else {
+        quote! {
+            {
+                let inner = &self.#field_ident;
+                #push_label
+                index_key.composite.push_str(&#key_part);
+            }
+        }
+    })
+}
+
+/// Reads the encoding options of a composite, declared by the field attribute
+/// `attr`.
+fn key_encoding(
+    field: &syn::Field,
+    attr: &syn::Attribute,
+    pad: Option<usize>,
+    sortable: bool,
+    time: Option<String>,
+) -> syn::Result<KeyEncoding> {
+    if [pad.is_some(), sortable, time.is_some()].iter().filter(|x| **x).count() > 1 {
+        return Err(syn::Error::new_spanned(
+            attr,
+            "only one of `pad`, `sortable` and `time` can be used",
+        ));
+    }
+    // padding signed numbers or strings would not sort, nor parse back
+    if pad.is_some() {
+        let field_type = FieldType::new(&field.ty)?;
+        let unsigned = matches!(field_type.inner_name.as_str(), "u8" | "u16" | "u32" | "u64" | "u128" | "usize");
+        if !unsigned || field_type.is_vec || field_type.is_set || field_type.is_map {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "`pad` can only be used with unsigned integer fields, use `sortable` for signed numbers",
+            ));
+        }
+    }
+    if let Some(time) = time.as_deref() {
+        if !matches!(time, "iso8601" | "epoch_seconds" | "epoch_millis") {
+            return Err(syn::Error::new_spanned(
+                attr,
+                "time must be one of `iso8601`, `epoch_seconds` or `epoch_millis`",
+            ));
+        }
+    }
+    Ok(KeyEncoding { pad, sortable, time })
+}
+
+/// Adds a field to the composite of an index key, declared by the field
+/// attribute `attr`.
+fn add_composite(
+    index_meta: &mut HashMap<String, IndexKeys>,
+    field: &syn::Field,
+    attr: &syn::Attribute,
+    index: &str,
+    key: &str,
+    position: usize,
+    encoding: KeyEncoding,
+) -> syn::Result<()> {
+    let index_keys = match index_meta.get_mut(index) {
+        Some(index_keys) => index_keys,
+        None => {
+            return Err(syn::Error::new_spanned(
+                attr,
+                format!("unknown index `{}`, declare it in deez_schema or with deez_index", index),
+            ))
+        }
+    };
+    let index_key = match key {
+        "hash" => &mut index_keys.hash,
+        "range" => match index_keys.range.as_mut() {
+            Some(range) => range,
+            None => return Err(syn::Error::new_spanned(attr, format!("index `{}` has no range key", index))),
+        },
+        _ => return Err(syn::Error::new_spanned(attr, "key must be either `hash` or `range`")),
+    };
+    if index_key.composite.iter().any(|c| c.position == position) {
+        return Err(syn::Error::new_spanned(
+            attr,
+            format!("duplicate position {} in {} {} key", position, index, key),
+        ));
+    }
+    index_key.composite.push(Composite {
+        position,
+        syn_field: field.clone(),
+        span: attr.span(),
+        encoding,
+    });
+    Ok(())
+}
+
+/// Generates the code converting a field, as `(insert, ref_insert, read)`.
+/// `insert` consumes `value` while `ref_insert` borrows `value_ref`, and `read`
+/// reads the field's attribute from `item`.
+fn field_conversion(
+    field: &syn::Field,
+    entity: &str,
+    field_name: &str,
+    value: proc_macro2::TokenStream,
+    value_ref: proc_macro2::TokenStream,
+) -> syn::Result<(proc_macro2::TokenStream, proc_macro2::TokenStream, proc_macro2::TokenStream)> {
+    let as_set = match field.attrs.iter().find(|a| a.path().is_ident("deez_vec")) {
+        Some(attr) => match DeezVec::from_attributes(&field.attrs)?.dynamo_type.as_str() {
+            "list" => false,
+            "set" => true,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    attr,
+                    "dynamo_type must be either `list` or `set`",
+                ))
+            }
+        },
+        None => false,
+    };
+    let time_format = DeezTime::from_attributes(&field.attrs)?.format;
+    if let Some(format) = &time_format {
+        if !matches!(format.as_str(), "iso8601" | "epoch_seconds" | "epoch_millis") {
+            let attr = field.attrs.iter().find(|a| a.path().is_ident("deez_time")).unwrap();
+            return Err(syn::Error::new_spanned(
+                attr,
+                "format must be one of `iso8601`, `epoch_seconds` or `epoch_millis`",
+            ));
+        }
+    }
+
+    let converted = as_set || time_format.is_some();
+    let (insert, ref_insert, read) = if converted {
+        // stored differently from the field type's `IntoAttributeValue`
+        let field_type = FieldType::new(&field.ty)?;
+        if as_set && !field_type.is_vec {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                "`deez_vec` can only be used with `Vec` fields",
+            ));
+        }
+        if as_set && field_type.inner_name == "bool" {
+            return Err(syn::Error::new_spanned(
+                &field.ty,
+                "`bool` can not be stored in a DynamoDB set",
+            ));
+        }
+        let conversion = conversion(field, &field_type, entity, field_name)?;
+        let insert: proc_macro2::TokenStream;
+        let read: proc_macro2::TokenStream;
+        insert_read!(
+            value, false, field_name, entity, field_type, field.attrs, conversion;
+            insert, read
+        );
+        let ref_insert: proc_macro2::TokenStream;
+        let _read: proc_macro2::TokenStream;
+        insert_read!(
+            value_ref, true, field_name, entity, field_type, field.attrs, conversion;
+            ref_insert, _read
+        );
+        (insert, ref_insert, read)
+    } else {
+        // trait errors point at the field's type
+        let span = field.ty.span();
+        (
+            quote_spanned! {span=> IntoAttributeValue::into_av(#value) },
+            quote_spanned! {span=> IntoAttributeValue::to_av(#value_ref) },
+            quote_spanned! {span=> FromAttributeValue::from_av(av).map_err(|e| e.in_field(#entity, #field_name))? },
+        )
+    };
+
+    let get = match DeezField::from_attributes(&field.attrs)?.alias {
+        Some(alias) => quote! { item.get(#field_name).or_else(|| item.get(#alias)) },
+        None => quote! { item.get(#field_name) },
+    };
+    // absent optional attributes are read as `None`, and absent or `NULL`
+    // collections as empty ones, unless a `deez_default` is given
+    let collection = FieldType::new(&field.ty).map(|t| t.is_vec || t.is_set || t.is_map).unwrap_or(false);
+    let absent = if collection {
+        quote! { Some(AttributeValue::Null(_)) | None }
+    } else {
+        quote! { None }
+    };
+    let missing = quote! {
+        return Err(DeezError::MissingAttribute {
+            entity: #entity.to_string(),
+            field: #field_name.to_string(),
+        })
+    };
+    let ty = &field.ty;
+    let default = match deez_default(field)? {
+        Some(default) => default,
+        None if is_option(&field.ty) => quote! { None },
+        None if collection => quote! { Default::default() },
+        None if converted => missing,
+        // collections behind an alias are only known by their trait impl
+        None => quote! {
+            match <#ty as FromAttributeValue>::from_absent() {
+                Some(value) => value,
+                None => #missing,
+            }
+        },
+    };
+    let read = quote! {
+        match #get {
+            #absent => #default,
+            Some(av) => #read,
+        }
+    };
+
+    Ok((insert, ref_insert, read))
+}
+
+/// The value of a field whose attribute is absent, given by `#[deez_default]`
+/// or `#[deez_default = "path::to::fn"]`.
+fn deez_default(field: &syn::Field) -> syn::Result<Option<proc_macro2::TokenStream>> {
+    let attr = match field.attrs.iter().find(|a| a.path().is_ident("deez_default")) {
+        Some(attr) => attr,
+        None => return Ok(None),
+    };
+    match &attr.meta {
+        syn::Meta::Path(_) => Ok(Some(quote! { Default::default() })),
+        syn::Meta::NameValue(syn::MetaNameValue {
+            value: syn::Expr::Lit(syn::ExprLit {
+                lit: syn::Lit::Str(s), ..
+            }),
+            ..
+        }) => {
+            let path: syn::Path = s.parse()?;
+            Ok(Some(quote! { #path() }))
+        }
+        _ => Err(syn::Error::new_spanned(
+            attr,
+            "expected `#[deez_default]` or `#[deez_default = \"path::to::fn\"]`",
+        )),
+    }
+}
+
+/// Generates the statement inserting a field's `AttributeValue` into `m`,
+/// handling empty collections according to `#[deez_field(empty = "...")]`.
+/// DynamoDB rejects empty sets, so those are omitted by default.
+fn empty_insert(
+    field: &syn::Field,
+    field_name: &str,
+    av: proc_macro2::TokenStream,
+) -> syn::Result<proc_macro2::TokenStream> {
+    let sets = quote! {
+        AttributeValue::Ss(v) => v.is_empty(),
+        AttributeValue::Ns(v) => v.is_empty(),
+        AttributeValue::Bs(v) => v.is_empty(),
+    };
+    let collections = quote! {
+        #sets
+        AttributeValue::L(v) => v.is_empty(),
+        AttributeValue::M(v) => v.is_empty(),
+    };
+
+    let insert = match DeezField::from_attributes(&field.attrs)?.empty.as_deref() {
+        Some("omit") => quote! {
+            let av = #av;
+            if !match &av { #collections _ => false } {
+                m.insert(#field_name.to_string(), av);
+            }
+        },
+        Some("null") => quote! {
+            let av = #av;
+            let av = if match &av { #collections _ => false } { AttributeValue::Null(true) } else { av };
+            m.insert(#field_name.to_string(), av);
+        },
+        Some(_) => {
+            let attr = field.attrs.iter().find(|a| a.path().is_ident("deez_field")).unwrap();
+            return Err(syn::Error::new_spanned(attr, "empty must be either `omit` or `null`"));
+        }
+        // checked on the value rather than the field type, which may be an
+        // alias or a path the derive does not recognize as a set
+        None => quote! {
+            let av = #av;
+            if !match &av { #sets _ => false } {
+                m.insert(#field_name.to_string(), av);
+            }
+        },
+    };
+    Ok(quote! {{ #insert }})
+}
+
+fn is_option(ty: &syn::Type) -> bool {
+    FieldType::new(ty).map(|t| t.is_option).unwrap_or(false)
+}
+
+/// Element conversions for fields that are not stored according to their
+/// `IntoAttributeValue` implementation, i.e. vectors stored as sets and
+/// timestamps with a `deez_time` format.
+fn conversion(field: &syn::Field, field_type: &FieldType, entity: &str, field_name: &str) -> syn::Result<Conversion> {
+    let ty: syn::Type = syn::parse_str(&field_type.inner_name)?;
+    let format = match DeezTime::from_attributes(&field.attrs)?.format {
+        Some(format) => format,
+        None => {
+            return Ok(Conversion {
+                to_av: quote! { IntoAttributeValue::to_av },
+                from_av: quote! {
+                    |av: &AttributeValue| -> Result<#ty, DeezError> {
+                        FromAttributeValue::from_av(av).map_err(|e| e.in_field(#entity, #field_name))
+                    }
+                },
+                to_set: quote! { |x: Vec<&#ty>| SetAttributeValue::to_set(x) },
+                from_set: quote! {
+                    |av: &AttributeValue| -> Result<Vec<#ty>, DeezError> {
+                        SetAttributeValue::from_set(av).map_err(|e| e.in_field(#entity, #field_name))
+                    }
+                },
+            })
+        }
+    };
+
+    let invalid = quote! {
+        DeezError::InvalidTimestamp {
+            entity: #entity.to_string(),
+            field: #field_name.to_string(),
+            value: s.clone(),
+        }
+    };
+    let (to_string, parse) = match format.as_str() {
+        "iso8601" => (
+            quote! { |x: &#ty| Timestamp::to_iso8601(x) },
+            quote! { <#ty as Timestamp>::from_iso8601(s) },
+        ),
+        "epoch_seconds" => (
+            quote! { |x: &#ty| Timestamp::to_epoch_millis(x).div_euclid(1000).to_string() },
+            quote! {
+                s.parse::<i64>()
+                    .ok()
+                    .and_then(|n| n.checked_mul(1000))
+                    .and_then(<#ty as Timestamp>::from_epoch_millis)
+            },
+        ),
+        "epoch_millis" => (
+            quote! { |x: &#ty| Timestamp::to_epoch_millis(x).to_string() },
+            quote! { s.parse::<i64>().ok().and_then(<#ty as Timestamp>::from_epoch_millis) },
+        ),
+        // checked by `field_conversion`
+        _ => unreachable!(),
+    };
+    let parse = quote! {
+        |s: &String| -> Result<#ty, DeezError> {
+            #parse.ok_or_else(|| #invalid)
+        }
+    };
+
+    Ok(if format == "iso8601" {
+        Conversion {
+            to_av: quote! { |x: &#ty| AttributeValue::S((#to_string)(x)) },
+            from_av: from_av!(entity, field_name, S, quote! { #ty }, quote! { (#parse)(inner) }),
+            to_set: quote! {
+                |x: Vec<&#ty>| AttributeValue::Ss(x.into_iter().map(#to_string).collect())
+            },
+            from_set: from_av!(
+                entity,
+                field_name,
+                Ss,
+                quote! { Vec<#ty> },
+                quote! { inner.iter().map(#parse).collect() }
+            ),
+        }
+    } else {
+        Conversion {
+            to_av: quote! { |x: &#ty| AttributeValue::N((#to_string)(x)) },
+            from_av: from_av!(entity, field_name, N, quote! { #ty }, quote! { (#parse)(inner) }),
+            to_set: quote! {
+                |x: Vec<&#ty>| AttributeValue::Ns(x.into_iter().map(#to_string).collect())
+            },
+            from_set: from_av!(
+                entity,
+                field_name,
+                Ns,
+                quote! { Vec<#ty> },
+                quote! { inner.iter().map(#parse).collect() }
+            ),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::derive_deez;
+    use crate::types::FieldType;
+    use quote::quote;
+    use syn::parse_quote;
+
+    fn error(input: syn::DeriveInput) -> String {
+        match derive_deez(input) {
+            Ok(_) => panic!("expected an error"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn schema_errors() {
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_schema(gsi1_name = "gsi1", gsi1_range = "gsi1sk")]
+                struct Foo {
+                    #[deez_primary(key = "hash")]
+                    id: Option<String>,
+                }
+            }),
+            "`gsi1_hash` not provided in deez_schema"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                struct Foo {
+                    id: Option<String>,
+                }
+            }),
+            "`primary_hash` not provided in deez_schema"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(rename_all = "Title Case")]
+                struct Foo {
+                    id: Option<String>,
+                }
+            }),
+            "unknown rename_all rule `Title Case`"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                struct Foo {
+                    #[deez_primary(key = "hash")]
+                    id: Option<String>,
+                }
+            }),
+            "index attributes require `table`, `service`, `entity` and primary keys in deez_schema"
+        );
+    }
+
+    #[test]
+    fn index_errors() {
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                struct Foo {
+                    #[deez_gsi2(key = "hash")]
+                    id: Option<String>,
+                }
+            }),
+            "unknown index `gsi2`, declare it in deez_schema or with deez_index"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_index(name = "by_id", index = "gsi1", hash = "gsi1pk", range = "gsi1sk")]
+                struct Foo {
+                    #[deez_key(index = "by_name", key = "hash")]
+                    id: Option<String>,
+                }
+            }),
+            "unknown index `by_name`, declare it in deez_schema or with deez_index"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                struct Foo {
+                    #[deez_primary(key = "range", pad = 4, sortable)]
+                    seq: u32,
+                }
+            }),
+            "only one of `pad`, `sortable` and `time` can be used"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk", primary_range_type = "numeric")]
+                struct Foo {
+                    #[deez_primary(key = "range")]
+                    score: u32,
+                }
+            }),
+            "key type must be one of `string`, `number` or `binary`"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk", primary_range_type = "number")]
+                struct Foo {
+                    #[deez_primary(key = "range")]
+                    score: u32,
+                    #[deez_primary(key = "range", position = 1)]
+                    rank: u32,
+                }
+            }),
+            "number keys must have exactly one composite field"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk", primary_range_type = "number")]
+                struct Foo {
+                    #[deez_primary(key = "range", sortable)]
+                    score: i32,
+                }
+            }),
+            "`pad` and `sortable` can only be used with string keys"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk")]
+                #[deez_index(name = "by_score", index = "gsi1", hash = "gsi1pk", range_type = "number")]
+                struct Foo {
+                    score: u32,
+                }
+            }),
+            "index `by_score` has no range key"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_index(name = "by_time", index = "gsi1", hash = "gsi1pk", range = "gsi1sk")]
+                struct Foo {
+                    #[deez_key(index = "by_time", key = "range", time = "epoch")]
+                    at: Option<SystemTime>,
+                }
+            }),
+            "time must be one of `iso8601`, `epoch_seconds` or `epoch_millis`"
+        );
+
+        for ty in [quote! { i32 }, quote! { Option<String> }, quote! { f64 }] {
+            assert_eq!(
+                error(parse_quote! {
+                    #[deez_schema(table = "t", service = "s", entity = "e")]
+                    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                    struct Foo {
+                        #[deez_primary(key = "range", pad = 6)]
+                        seq: #ty,
+                    }
+                }),
+                "`pad` can only be used with unsigned integer fields, use `sortable` for signed numbers"
+            );
+        }
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_index(name = "primary", index = "gsi1", hash = "gsi1pk", range = "gsi1sk")]
+                struct Foo {
+                    id: Option<String>,
+                }
+            }),
+            "index `primary` is already declared"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                struct Foo {
+                    #[deez_primary(key = "sort")]
+                    id: Option<String>,
+                }
+            }),
+            "key must be either `hash` or `range`"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk")]
+                struct Foo {
+                    #[deez_primary(key = "range")]
+                    id: Option<String>,
+                }
+            }),
+            "index `primary` has no range key"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk")]
+                #[deez_index(name = "by_x", index = "lsi1", range = "lsi1sk", local = true)]
+                struct Foo {
+                    id: Option<String>,
+                }
+            }),
+            "local secondary indexes require `primary_range` in deez_schema"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_schema(primary_hash_template = "ORG#${org}")]
+                struct Foo {
+                    org_id: String,
+                }
+            }),
+            "`org` in template `ORG#${org}` is not a field"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_schema(primary_hash_template = "ORG#${org_id}${user_id}")]
+                struct Foo {
+                    org_id: String,
+                    user_id: String,
+                }
+            }),
+            "placeholders in template `ORG#${org_id}${user_id}` must be separated by text"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_index(name = "by_seq", index = "gsi1", hash = "gsi1pk", hash_template = "SEQ#${seq}")]
+                struct Foo {
+                    #[deez_primary(key = "range", pad = 6)]
+                    seq: u32,
+                }
+            }),
+            "`seq` has a key encoding and can not be used in the template `SEQ#${seq}`"
+        );
+
+        for (field, key_type, message) in [
+            (
+                quote! { name: String },
+                quote! { range_type = "number" },
+                "number keys require an integer, `f64`, `Number` or `Decimal` field, or an epoch timestamp",
+            ),
+            (
+                quote! { ratio: f32 },
+                quote! { range_type = "number" },
+                "number keys require an integer, `f64`, `Number` or `Decimal` field, or an epoch timestamp",
+            ),
+            (
+                quote! { #[deez_time(format = "iso8601")] at: SystemTime },
+                quote! { range_type = "number" },
+                "`iso8601` timestamps can not be used in number keys",
+            ),
+            (quote! { name: String }, quote! { range_type = "binary" }, "binary keys require a `Blob` field"),
+        ] {
+            assert_eq!(
+                error(parse_quote! {
+                    #[deez_schema(table = "t", service = "s", entity = "e")]
+                    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                    #[deez_index(name = "by_x", index = "gsi1", hash = "gsi1pk", range = "gsi1sk", #key_type)]
+                    struct Foo {
+                        #[deez_key(index = "by_x", key = "range")]
+                        #field,
+                    }
+                }),
+                message
+            );
+        }
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_schema(primary_hash_template = "ORG#${org_id")]
+                struct Foo {
+                    org_id: String,
+                }
+            }),
+            "unclosed placeholder in template `ORG#${org_id`"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_schema(primary_hash_template = "ORG\\${org_id}")]
+                struct Foo {
+                    org_id: String,
+                }
+            }),
+            "template `ORG\\${org_id}` can not contain `\\`"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_schema(primary_hash_template = "ORG#${org_id}")]
+                struct Foo {
+                    #[deez_primary(key = "hash")]
+                    org_id: String,
+                }
+            }),
+            "`pk` is declared by the template `ORG#${org_id}`"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range_template = "USER#${id}")]
+                struct Foo {
+                    id: String,
+                }
+            }),
+            "index `primary` has no range key"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk", key_casing = "title")]
+                struct Foo {
+                    id: String,
+                }
+            }),
+            "`key_casing` must be one of `upper`, `lower` or `none`"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_index(name = "by_x", index = "lsi1", local = true)]
+                struct Foo {
+                    id: Option<String>,
+                }
+            }),
+            "local secondary indexes require a `range` key"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_index(name = "by_x", index = "lsi1", hash = "pk", range = "lsi1sk", local = true)]
+                struct Foo {
+                    id: Option<String>,
+                }
+            }),
+            "local secondary indexes share the primary hash key"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_index(name = "by_x", index = "lsi1", range = "lsi1sk", local = true)]
+                struct Foo {
+                    #[deez_key(index = "by_x", key = "hash")]
+                    id: Option<String>,
+                }
+            }),
+            "local secondary indexes share the primary hash key"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                #[deez_index(name = "by_x", index = "gsi1", range = "gsi1sk")]
+                struct Foo {
+                    id: Option<String>,
+                }
+            }),
+            "`hash` not provided in deez_index"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                struct Foo {
+                    #[deez_primary(key = "range")]
+                    a: Option<String>,
+                    #[deez_primary(key = "range")]
+                    b: Option<String>,
+                }
+            }),
+            "duplicate position 0 in primary range key"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                #[deez_schema(table = "t", service = "s", entity = "e")]
+                #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+                struct Foo {
+                    #[deez_primary(key = "range")]
+                    a: Option<String>,
+                    #[deez_primary(key = "range", position = 2)]
+                    b: Option<String>,
+                }
+            }),
+            "missing composite for `sk` at position 1"
+        );
+    }
+
+    #[test]
+    fn field_errors() {
+        assert_eq!(
+            error(parse_quote! {
+                struct Foo {
+                    #[deez_vec(dynamo_type = "set")]
+                    flags: Vec<bool>,
+                }
+            }),
+            "`bool` can not be stored in a DynamoDB set"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                struct Foo {
+                    #[deez_vec(dynamo_type = "map")]
+                    flags: Vec<String>,
+                }
+            }),
+            "dynamo_type must be either `list` or `set`"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                struct Foo {
+                    #[deez_time(format = "rfc2822")]
+                    at: SystemTime,
+                }
+            }),
+            "format must be one of `iso8601`, `epoch_seconds` or `epoch_millis`"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                struct Foo {
+                    #[deez_field(skip_none)]
+                    name: String,
+                }
+            }),
+            "`skip_none` can only be used with `Option` fields"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                struct Foo {
+                    #[deez_field(empty = "skip")]
+                    names: Vec<String>,
+                }
+            }),
+            "empty must be either `omit` or `null`"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                struct Foo {
+                    #[deez_default(value = 1)]
+                    count: u32,
+                }
+            }),
+            "expected `#[deez_default]` or `#[deez_default = \"path::to::fn\"]`"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                struct Foo(String);
+            }),
+            "Deez can only be derived for structs with named fields"
+        );
+
+        assert_eq!(
+            error(parse_quote! {
+                enum Foo {
+                    Pair(String, String),
+                }
+            }),
+            "tuple variants must have exactly one field"
+        );
+
+        for ty in [quote! { String }, quote! { Option<Bar> }, quote! { Vec<Bar> }] {
+            assert_eq!(
+                error(parse_quote! {
+                    #[deez_enum(tag = "kind")]
+                    enum Foo {
+                        Name(#ty),
+                    }
+                }),
+                "tuple variants of a tagged enum must wrap a struct deriving `Deez`"
+            );
+        }
+    }
+
+    #[test]
+    fn field_types() {
+        for ty in [
+            parse_quote!(Option<Vec<u8>>),
+            parse_quote!(std::option::Option<Vec<u8>>),
+            parse_quote!(::core::option::Option<Vec<u8>>),
+        ] {
+            let t = FieldType::new(&ty).unwrap();
+            assert!(t.is_option && t.is_vec);
+            assert_eq!(t.inner_name, "u8");
+        }
+
+        let t = FieldType::new(&parse_quote!(my::Option)).unwrap();
+        assert!(!t.is_option);
+        let t = FieldType::new(&parse_quote!(std::collections::HashSet<String>)).unwrap();
+        assert!(!t.is_option && t.is_set);
+        assert_eq!(t.inner_name, "String");
+        let t = FieldType::new(&parse_quote!(Option<std::vec::Vec<u32>>)).unwrap();
+        assert!(t.is_option && t.is_vec);
+        let t = FieldType::new(&parse_quote!(std::collections::BTreeMap<std::string::String, u32>)).unwrap();
+        assert!(t.is_map);
+        assert_eq!(t.inner_name, "u32");
+        let t = FieldType::new(&parse_quote!(HashMap<u32, u32>)).unwrap();
+        assert!(!t.is_map);
+    }
 }
@@ -12,10 +12,12 @@ macro_rules! insert_index {
                     field: $hash_name,
                     ..Default::default()
                 },
-                range: IndexKey {
-                    field: $range_name,
+                range: $range_name.map(|field| IndexKey {
+                    field,
                     ..Default::default()
-                },
+                }),
+                local: false,
+                sparse: false,
             },
         );
     };
@@ -26,28 +28,31 @@ macro_rules! insert_gsi {
     (
         $index_meta:ident,
         $index_name_fns:ident,
+        $attrs:expr,
         $index:expr,
         $index_name:expr,
         $hash_name:expr,
         $range_name:expr
     ) => {
-        if let Some(index_name) = $index_name {
-            let hash_name = match $hash_name {
-                Some(x) => x,
-                None => panic!("hash key name not provided for index: {}", $index),
-            };
-            let range_name = match $range_name {
-                Some(x) => x,
-                None => panic!("range key name not provided for index: {}", $index),
-            };
-            insert_index!($index_meta, $index, hash_name, range_name); // todo: remove unwrap
-            let index_name_fn_name = format_ident!("{}_name", $index);
-            $index_name_fns = quote! {
-                #$index_name_fns
-                pub fn #index_name_fn_name() -> String {
-                    #index_name.to_string()
-                }
-            };
+        match ($index_name, $hash_name, $range_name) {
+            (Some(index_name), Some(hash_name), range_name) => {
+                insert_index!($index_meta, $index, hash_name, range_name);
+                let index_name_fn_name = format_ident!("{}_name", $index);
+                $index_name_fns = quote! {
+                    #$index_name_fns
+                    pub fn #index_name_fn_name() -> String {
+                        #index_name.to_string()
+                    }
+                };
+            }
+            (None, None, None) => {}
+            (index_name, _, _) => {
+                let missing = if index_name.is_none() { "name" } else { "hash" };
+                return Err(syn::Error::new(
+                    schema_span(&$attrs, &format!("{}_name", $index)),
+                    format!("`{}_{}` not provided in deez_schema", $index, missing),
+                ));
+            }
         }
     };
 }
@@ -55,52 +60,86 @@ pub(crate) use insert_gsi;
 
 macro_rules! read_attr {
     ($index_meta:ident, $field:expr, $index_attr:ident, $index:expr) => {
-        if let Ok(attribute) = $index_attr::from_attributes(&$field.attrs) {
-            let composite = Composite {
-                position: attribute.position,
-                syn_field: $field.clone(),
-            };
-            if let Some(index) = $index_meta.get_mut($index) {
-                match attribute.key.as_str() {
-                    "hash" => index.hash.composite.push(composite),
-                    "range" => index.range.composite.push(composite),
-                    _ => panic!("key must be either `hash` or `range`"),
-                }
-            } else {
-                panic!("unknown index: {}", $index);
-            }
+        let attr_ident = format!("deez_{}", $index);
+        if let Some(attr) = $field.attrs.iter().find(|a| a.path().is_ident(&attr_ident)) {
+            let attribute = $index_attr::from_attributes(&$field.attrs)?;
+            add_composite(&mut $index_meta, $field, attr, $index, &attribute.key, attribute.position, KeyEncoding::default())?;
         }
     };
 }
 pub(crate) use read_attr;
 
 macro_rules! compose_key {
-    ($index_key: expr) => {{
-        let mut c = quote! {};
-
-        for (i, _) in $index_key.composite.iter().enumerate() {
-            let composite = match $index_key.composite.iter().find(|c| c.position == i) {
-                Some(c) => c,
-                None => panic!("missing composite for {} at position: {}", $index_key.field, i),
+    ($index_key: expr, $rename_all: expr, $fields: expr, $prefix: expr, $key_format: expr) => {{
+        let mut c;
+
+        if let Some((key_type, span)) = &$index_key.key_type {
+            // number and binary keys hold the value of a single field
+            let composite = match (&$index_key.template, $index_key.composite.as_slice()) {
+                (None, [composite]) => composite,
+                _ => {
+                    return Err(syn::Error::new(
+                        *span,
+                        format!("{} keys must have exactly one composite field", key_type),
+                    ))
+                }
             };
-            let field_ident = match composite.syn_field.ident.as_ref() {
-                Some(i) => i,
-                None => panic!("could not parse field ident for index: {}", $index_key.field),
+            if composite.encoding.pad.is_some() || composite.encoding.sortable {
+                return Err(syn::Error::new(
+                    composite.span,
+                    "`pad` and `sortable` can only be used with string keys",
+                ));
+            }
+            check_key_type(key_type, composite)?;
+            let variant = match key_type.as_str() {
+                // the SDK has a `KeyType` of its own
+                "number" => quote! { DeezKeyType::Number },
+                _ => quote! { DeezKeyType::Binary },
             };
-            let field_name = field_ident.to_string();
-
+            let push = push_key_part(&composite.syn_field, "", None, "", &composite.encoding)?;
             c = quote! {
-                #c
-                if let Some(inner) = &self.#field_ident {
-                    index_key.composite.push_str(&format!("#{}_{}", #field_name, inner.to_string()));
-                } else {
-                    return index_key;
-                }
+                index_key.key_type = #variant;
+                #push
             };
+        } else if let Some((template, span)) = &$index_key.template {
+            // the template declares the whole key
+            if let Some(composite) = $index_key.composite.first() {
+                return Err(syn::Error::new(
+                    composite.span,
+                    format!("`{}` is declared by the template `{}`", $index_key.field, template),
+                ));
+            }
+            c = compose_template(template, *span, $fields, $key_format)?;
+        } else {
+            let prefix = $prefix;
+            c = quote! { index_key.composite.push_str(#prefix); };
+
+            for (i, _) in $index_key.composite.iter().enumerate() {
+                let composite = match $index_key.composite.iter().find(|c| c.position == i) {
+                    Some(c) => c,
+                    None => {
+                        // positions are unique, so one of them is out of range
+                        let c = $index_key.composite.iter().find(|c| c.position >= $index_key.composite.len()).unwrap();
+                        return Err(syn::Error::new(
+                            c.span,
+                            format!("missing composite for `{}` at position {}", $index_key.field, i),
+                        ));
+                    }
+                };
+                let field_name = attribute_name(&composite.syn_field, $rename_all)?;
+                let label = $key_format.label(&field_name, i == 0);
+                let push = push_key_part(&composite.syn_field, &label, Some($key_format), "", &composite.encoding)?;
+
+                c = quote! {
+                    #c
+                    #push
+                };
+            }
         }
 
         c = quote! {
             #c
+            index_key.complete = true;
             return index_key;
         };
 
@@ -109,63 +148,191 @@ macro_rules! compose_key {
 }
 pub(crate) use compose_key;
 
+macro_rules! key_part {
+    ($field:expr, $encoding:expr, $padded:expr) => {{
+        let encoding: &KeyEncoding = $encoding;
+        let time = match &encoding.time {
+            Some(time) => Some(time.clone()),
+            None => DeezTime::from_attributes(&$field.attrs)?.format,
+        };
+        // epoch values are zero-padded so they sort lexicographically, unless
+        // they are the value of a number key
+        match time.as_deref() {
+            Some("epoch_seconds") if !$padded => quote! { Timestamp::to_epoch_millis(inner).div_euclid(1000).to_string() },
+            Some("epoch_millis") if !$padded => quote! { Timestamp::to_epoch_millis(inner).to_string() },
+            Some("epoch_seconds") => quote! { epoch_key_part(Timestamp::to_epoch_millis(inner).div_euclid(1000), 10) },
+            Some("epoch_millis") => quote! { epoch_key_part(Timestamp::to_epoch_millis(inner), 13) },
+            Some(_) => quote! { Timestamp::to_iso8601(inner) },
+            None if encoding.sortable => quote! { SortableKeyPart::to_sortable_key_part(inner) },
+            None => match encoding.pad {
+                Some(width) => quote! { format!("{:0>1$}", IntoAttributeValue::to_key_part(inner), #width) },
+                None => quote! { IntoAttributeValue::to_key_part(inner) },
+            },
+        }
+    }};
+}
+pub(crate) use key_part;
+
+macro_rules! from_key_part {
+    ($field:expr, $ty:expr, $entity:expr, $field_name:expr, $encoding:expr, $padded:expr) => {{
+        let ty = &$ty;
+        let entity = &$entity;
+        let field_name = &$field_name;
+        let encoding: &KeyEncoding = $encoding;
+        let time = match &encoding.time {
+            Some(time) => Some(time.clone()),
+            None => DeezTime::from_attributes(&$field.attrs)?.format,
+        };
+        // the inverse of `key_part!`, reading `value`
+        let timestamp = match time.as_deref() {
+            Some("epoch_seconds") if !$padded => Some(quote! {
+                value.parse::<i64>().ok().and_then(|s| s.checked_mul(1000)).and_then(<#ty as Timestamp>::from_epoch_millis)
+            }),
+            Some("epoch_millis") if !$padded => Some(quote! {
+                value.parse::<i64>().ok().and_then(<#ty as Timestamp>::from_epoch_millis)
+            }),
+            Some("epoch_seconds") => Some(quote! {
+                parse_epoch_key_part(&value, 10).and_then(|s| s.checked_mul(1000)).and_then(<#ty as Timestamp>::from_epoch_millis)
+            }),
+            Some("epoch_millis") => Some(quote! {
+                parse_epoch_key_part(&value, 13).and_then(<#ty as Timestamp>::from_epoch_millis)
+            }),
+            Some(_) => Some(quote! { <#ty as Timestamp>::from_iso8601(&value) }),
+            None => None,
+        };
+        match timestamp {
+            Some(timestamp) => quote! {
+                #timestamp.ok_or_else(|| DeezError::InvalidTimestamp {
+                    entity: #entity.to_string(),
+                    field: #field_name.to_string(),
+                    value: value.clone(),
+                })
+            },
+            None if encoding.sortable => quote! {
+                <#ty as SortableKeyPart>::from_sortable_key_part(&value).map_err(|e| e.in_field(#entity, #field_name))
+            },
+            None if encoding.pad.is_some() => quote! {
+                <#ty as FromAttributeValue>::from_key_part(unpad_key_part(&value)).map_err(|e| e.in_field(#entity, #field_name))
+            },
+            None => quote! {
+                <#ty as FromAttributeValue>::from_key_part(&value).map_err(|e| e.in_field(#entity, #field_name))
+            },
+        }
+    }};
+}
+pub(crate) use from_key_part;
+
+macro_rules! unexpected_variant {
+    ($entity:expr, $field_name:expr, $expected:expr) => {{
+        let entity = &$entity;
+        let field_name = &$field_name;
+        let expected = $expected;
+        quote! {
+            DeezError::UnexpectedVariant {
+                entity: #entity.to_string(),
+                field: #field_name.to_string(),
+                expected: #expected,
+            }
+        }
+    }};
+}
+pub(crate) use unexpected_variant;
+
+macro_rules! from_av {
+    ($entity:expr, $field_name:expr, $variant:ident, $ty:expr, $e:expr) => {{
+        let ty = $ty;
+        let e = $e;
+        let unexpected = unexpected_variant!($entity, $field_name, stringify!($variant));
+        quote! {
+            |av: &AttributeValue| -> Result<#ty, DeezError> {
+                match av {
+                    AttributeValue::$variant(inner) => #e,
+                    _ => Err(#unexpected),
+                }
+            }
+        }
+    }};
+}
+pub(crate) use from_av;
+
 macro_rules! insert_read {
     (
-        $field_ident:ident, $field_type:ident, $field_attrs:expr, $insert:ident, $read:ident;
-
-        insert_list: $insert_list:expr,
-        read_list: $read_list:expr,
-        insert_set: $insert_set:expr,
-        read_set: $read_set:expr,
-        insert_scalar: $insert_scalar:expr,
-        read_scalar: $read_scalar:expr,
-        insert_option_list: $insert_option_list:expr,
-        read_option_list: $read_option_list:expr,
-        insert_option_set: $insert_option_set:expr,
-        read_option_set: $read_option_set:expr,
-        insert_option_scalar: $insert_option_scalar:expr,
-        read_option_scalar: $read_option_scalar:expr
+        $value:ident, $by_ref:expr, $field_name:ident, $entity:ident, $field_type:ident, $field_attrs:expr, $conversion:ident;
+        $insert:ident, $read:ident
     ) => {
-        if $field_type.is_option {
-            if $field_type.is_vec {
-                let deez_vec = DeezVec::from_attributes(&$field_attrs).unwrap();
-                $insert = match deez_vec.dynamo_type.as_str() {
-                    "list" => $insert_option_list,
-                    "set" => $insert_option_set,
-                    _ => panic!("dynamo_type must be either `list` or `set`"),
-                };
-                $read = match deez_vec.dynamo_type.as_str() {
-                    "list" => $read_option_list,
-                    "set" => $read_option_set,
-                    _ => panic!("dynamo_type must be either `list` or `set`"),
-                };
-            } else {
-                $insert = $insert_option_scalar;
-                $read = $read_option_scalar;
+        let to_av = &$conversion.to_av;
+        let from_av = &$conversion.from_av;
+        // `$value` is either the field or a reference to it
+        let (inner_ref, map_entries) = if $by_ref {
+            (quote! { inner }, quote! { inner.iter().map(|(k, v)| (k.clone(), (#to_av)(v))) })
+        } else {
+            (quote! { &inner }, quote! { inner.into_iter().map(|(k, v)| (k, (#to_av)(&v))) })
+        };
+        let (inner_insert, inner_read) = if $field_type.is_vec {
+            let as_set = match DeezVec::from_attributes(&$field_attrs) {
+                Ok(deez_vec) => deez_vec.dynamo_type == "set",
+                Err(_) => false,
             };
+            if as_set {
+                let to_set = &$conversion.to_set;
+                let from_set = &$conversion.from_set;
+                (quote! { (#to_set)(inner.iter().collect()) }, quote! { (#from_set)(av)? })
+            } else {
+                let unexpected = unexpected_variant!($entity, $field_name, "L");
+                (
+                    quote! { AttributeValue::L(inner.iter().map(#to_av).collect()) },
+                    quote! {
+                        match av {
+                            AttributeValue::L(l) => l.iter().map(#from_av).collect::<Result<Vec<_>, DeezError>>()?,
+                            _ => return Err(#unexpected),
+                        }
+                    },
+                )
+            }
+        } else if $field_type.is_set {
+            let to_set = &$conversion.to_set;
+            let from_set = &$conversion.from_set;
+            (
+                quote! { (#to_set)(inner.iter().collect()) },
+                quote! { (#from_set)(av)?.into_iter().collect() },
+            )
+        } else if $field_type.is_map {
+            let unexpected = unexpected_variant!($entity, $field_name, "M");
+            (
+                quote! { AttributeValue::M(#map_entries.collect()) },
+                quote! {
+                    match av {
+                        AttributeValue::M(m) => m
+                            .iter()
+                            .map(|(k, v)| (#from_av)(v).map(|v| (k.clone(), v)))
+                            .collect::<Result<_, DeezError>>()?,
+                        _ => return Err(#unexpected),
+                    }
+                },
+            )
+        } else {
+            (quote! { (#to_av)(#inner_ref) }, quote! { (#from_av)(av)? })
+        };
+
+        if $field_type.is_option {
             $insert = quote! {
-                match item.#$field_ident {
-                    Some(inner) => #$insert,
+                match #$value {
+                    Some(inner) => #inner_insert,
                     None => AttributeValue::Null(true),
                 }
             };
-        } else {
-            if $field_type.is_vec {
-                let deez_vec = DeezVec::from_attributes(&$field_attrs).unwrap();
-                $insert = match deez_vec.dynamo_type.as_str() {
-                    "list" => $insert_list,
-                    "set" => $insert_set,
-                    _ => panic!("dynamo_type must be either `list` or `set`"),
-                };
-                $read = match deez_vec.dynamo_type.as_str() {
-                    "list" => $read_list,
-                    "set" => $read_set,
-                    _ => panic!("dynamo_type must be either `list` or `set`"),
-                };
-            } else {
-                $insert = $insert_scalar;
-                $read = $read_scalar;
+            $read = quote! {
+                match av {
+                    AttributeValue::Null(_) => None,
+                    av => Some(#inner_read),
+                }
             };
+        } else {
+            $insert = quote! {{
+                let inner = #$value;
+                #inner_insert
+            }};
+            $read = inner_read;
         }
     };
 }
@@ -179,6 +346,33 @@ macro_rules! attr_derive {
             dynamo_type: String,
         }
 
+        #[derive(Attribute, Debug)]
+        #[attribute(ident = deez_enum)]
+        struct DeezEnum {
+            tag: Option<String>,
+        }
+
+        #[derive(Attribute, Debug)]
+        #[attribute(ident = deez_variant)]
+        struct DeezVariant {
+            rename: Option<String>,
+        }
+
+        #[derive(Attribute, Debug)]
+        #[attribute(ident = deez_field)]
+        struct DeezField {
+            rename: Option<String>,
+            alias: Option<String>,
+            skip_none: bool,
+            empty: Option<String>,
+        }
+
+        #[derive(Attribute, Debug)]
+        #[attribute(ident = deez_time)]
+        struct DeezTime {
+            format: Option<String>,
+        }
+
         // todo: cant use empty struct???
         #[derive(Attribute, Debug)]
         #[attribute(ident = deez_ignore)]
@@ -191,12 +385,24 @@ macro_rules! attr_derive {
         #[attribute(ident = deez_schema)]
         // #[attribute(invalid_field = "ok")]
         struct DeezSchema {
-            service: String,
-            table: String,
-            entity: String,
+            service: Option<String>,
+            table: Option<String>,
+            entity: Option<String>,
 
-            primary_hash: String,
-            primary_range: String,
+            primary_hash: Option<String>,
+            primary_range: Option<String>,
+            primary_hash_template: Option<String>,
+            primary_range_template: Option<String>,
+            primary_hash_type: Option<String>,
+            primary_range_type: Option<String>,
+
+            rename_all: Option<String>,
+            skip_none: bool,
+
+            key_delimiter: Option<String>,
+            key_label_separator: Option<String>,
+            key_prefix: Option<bool>,
+            key_casing: Option<String>,
 
             gsi1_name: Option<String>,
             gsi1_hash: Option<String>,
@@ -279,11 +485,41 @@ macro_rules! attr_derive {
             gsi20_range: Option<String>,
         }
 
+        #[derive(Attribute, Debug)]
+        #[attribute(ident = deez_index)]
+        struct DeezIndex {
+            name: String,
+            index: String,
+            hash: Option<String>,
+            range: Option<String>,
+            local: bool,
+            sparse: bool,
+            hash_template: Option<String>,
+            range_template: Option<String>,
+            hash_type: Option<String>,
+            range_type: Option<String>,
+        }
+
+        #[derive(Attribute, Debug)]
+        #[attribute(ident = deez_key)]
+        struct DeezKey {
+            index: String,
+            #[attribute(default = 0)]
+            position: usize,
+            pad: Option<usize>,
+            sortable: bool,
+            time: Option<String>,
+            key: String,
+        }
+
         #[derive(Attribute, Debug)]
         #[attribute(ident = deez_primary)]
         struct DeezPrimary {
             #[attribute(default = 0)]
             position: usize,
+            pad: Option<usize>,
+            sortable: bool,
+            time: Option<String>,
             key: String,
         }
 
@@ -1,68 +1,195 @@
-use quote::ToTokens;
-use regex::Regex;
+use proc_macro2::{Span, TokenStream};
+use quote::{quote, ToTokens};
 use syn::{Field, Type};
 
 pub struct IndexKeys {
     pub hash: IndexKey,
-    pub range: IndexKey,
+    pub range: Option<IndexKey>,
+    /// Local secondary index, sharing the primary hash key.
+    pub local: bool,
+    /// Omit the index attributes when the hash key is incomplete.
+    pub sparse: bool,
+}
+
+/// Format of composed keys, set with the `key_*` options of `deez_schema`.
+pub struct KeyFormat {
+    pub delimiter: String,
+    pub label_separator: String,
+    /// Whether keys start with `$service#entity` or `$entity`.
+    pub prefix: bool,
+    /// `upper` or `lower`, applied to prefixes, labels and values.
+    pub casing: Option<String>,
+}
+
+impl KeyFormat {
+    pub fn hash_prefix(&self, service: &str, entity: &str) -> String {
+        match self.prefix {
+            true => self.case(&format!("${}{}{}", service, self.delimiter, entity)),
+            false => String::new(),
+        }
+    }
+
+    pub fn range_prefix(&self, entity: &str) -> String {
+        match self.prefix {
+            true => self.case(&format!("${}", entity)),
+            false => String::new(),
+        }
+    }
+
+    /// Label preceding the value of a field, without a delimiter at the start
+    /// of a key.
+    pub fn label(&self, field_name: &str, first: bool) -> String {
+        let delimiter = if first && !self.prefix { "" } else { &self.delimiter };
+        self.case(&format!("{}{}{}", delimiter, field_name, self.label_separator))
+    }
+
+    pub fn case(&self, s: &str) -> String {
+        match self.casing.as_deref() {
+            Some("upper") => s.to_uppercase(),
+            Some("lower") => s.to_lowercase(),
+            _ => s.to_string(),
+        }
+    }
+
+    /// Applies the casing to a `String` key part at runtime.
+    pub fn case_value(&self, value: TokenStream) -> TokenStream {
+        match self.casing.as_deref() {
+            Some("upper") => quote! { #value.to_uppercase() },
+            Some("lower") => quote! { #value.to_lowercase() },
+            _ => value,
+        }
+    }
 }
 
 #[derive(Default)]
 pub struct IndexKey {
     pub field: String,
     pub composite: Vec<Composite>,
+    /// Template replacing the default key format, and its span for errors.
+    pub template: Option<(String, Span)>,
+    /// `number` or `binary` for keys stored as `N` or `B` attributes, holding
+    /// the value of a single field, and its span for errors.
+    pub key_type: Option<(String, Span)>,
 }
 
+#[derive(Clone)]
 pub struct Composite {
     pub position: usize,
     pub syn_field: Field,
+    /// Span of the index attribute, for errors.
+    pub span: Span,
+    pub encoding: KeyEncoding,
+}
+
+/// Encoding of a composite's value, set with the `pad`, `sortable` and `time`
+/// options of its index attribute. At most one of them is set.
+#[derive(Clone, Default)]
+pub struct KeyEncoding {
+    /// Zero padding to a fixed width.
+    pub pad: Option<usize>,
+    /// Order-preserving encoding of `SortableKeyPart`.
+    pub sortable: bool,
+    /// Timestamp format, overriding the field's `deez_time`.
+    pub time: Option<String>,
+}
+
+/// Closures converting a single value of a field's inner type, emitted into the
+/// generated code and wrapped by `insert_read!` for lists, sets and options.
+pub struct Conversion {
+    /// `|x: &T| -> AttributeValue`
+    pub to_av: TokenStream,
+    /// `|av: &AttributeValue| -> Result<T, DeezError>`
+    pub from_av: TokenStream,
+    /// `|x: Vec<&T>| -> AttributeValue`
+    pub to_set: TokenStream,
+    /// `|av: &AttributeValue| -> Result<Vec<T>, DeezError>`
+    pub from_set: TokenStream,
 }
 
 #[derive(Default)]
 pub struct FieldType {
     pub is_option: bool,
     pub is_vec: bool,
+    /// `HashMap<String, T>` or `BTreeMap<String, T>`, with `inner_name` being `T`.
+    pub is_map: bool,
+    /// `HashSet<T>` or `BTreeSet<T>`, stored as a string, number or binary set.
+    pub is_set: bool,
     pub inner_name: String,
 }
 
 impl FieldType {
-    pub fn new(ty: &Type) -> FieldType {
+    pub fn new(ty: &Type) -> syn::Result<FieldType> {
         let mut s = FieldType::default();
-        if let syn::Type::Path(p) = ty {
-            let re_option = Regex::new(r"^Option (:: )?< (.*) >$").unwrap();
-            let re_vec = Regex::new(r"^Vec (:: )?< (.*) >$").unwrap();
-            match re_option.captures(&p.to_token_stream().to_string()) {
-                Some(x) => {
-                    s.is_option = true;
-                    let option_inner = x.get(2).unwrap().as_str();
-                    match re_vec.captures(&option_inner) {
-                        Some(x) => {
-                            s.is_vec = true;
-                            s.inner_name = x.get(2).unwrap().as_str().to_string();
-                        }
-                        None => {
-                            s.inner_name = option_inner.to_string();
-                        }
-                    }
+        let mut p = match ty {
+            syn::Type::Path(p) => p,
+            _ => return Err(syn::Error::new_spanned(ty, "could not parse field type as path")),
+        };
+        if let Some(inner) = generic_args(p, &["Option"]).and_then(|args| single(&args)) {
+            s.is_option = true;
+            p = match inner {
+                syn::Type::Path(inner) => inner,
+                _ => {
+                    s.inner_name = inner.to_token_stream().to_string();
+                    return Ok(s);
                 }
-                None => match re_vec.captures(&p.to_token_stream().to_string()) {
-                    Some(x) => {
-                        s.is_vec = true;
-                        s.inner_name = x.get(2).unwrap().as_str().to_string();
-                    }
-                    None => {
-                        s.inner_name = p.to_token_stream().to_string();
-                    }
-                },
-            }
-        } else {
-            panic!("could not parse field type as path");
+            };
         }
+        // collections are matched by the last segment of their path, so
+        // `std::vec::Vec<T>` is a `Vec<T>` too
+        let inner = if let Some(inner) = generic_args(p, &["Vec"]).and_then(|args| single(&args)) {
+            s.is_vec = true;
+            inner
+        } else if let Some(args) = generic_args(p, &["HashMap", "BTreeMap"]).filter(|args| is_string_key(args)) {
+            s.is_map = true;
+            args[1]
+        } else if let Some(inner) = generic_args(p, &["HashSet", "BTreeSet"]).and_then(|args| single(&args)) {
+            s.is_set = true;
+            inner
+        } else {
+            s.inner_name = p.to_token_stream().to_string();
+            return Ok(s);
+        };
+        s.inner_name = inner.to_token_stream().to_string();
 
-        s
+        Ok(s)
     }
 
     // pub fn inner_ident(&self) -> proc_macro2::Ident {
     //     format_ident!("{}", self.inner_name)
     // }
 }
+
+/// The type arguments of `p` if the last segment of its path is one of `names`,
+/// e.g. `T` of `Option<T>`, also when written as `std::option::Option<T>`.
+fn generic_args<'a>(p: &'a syn::TypePath, names: &[&str]) -> Option<Vec<&'a Type>> {
+    let segment = p.path.segments.last()?;
+    if p.qself.is_some() || !names.iter().any(|name| segment.ident == name) {
+        return None;
+    }
+    match &segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => args
+            .args
+            .iter()
+            .map(|arg| match arg {
+                syn::GenericArgument::Type(ty) => Some(ty),
+                _ => None,
+            })
+            .collect(),
+        _ => None,
+    }
+}
+
+fn single<'a>(args: &[&'a Type]) -> Option<&'a Type> {
+    match args {
+        [ty] => Some(ty),
+        _ => None,
+    }
+}
+
+/// Whether the map type arguments are `String, T`.
+fn is_string_key(args: &[&Type]) -> bool {
+    match args {
+        [syn::Type::Path(key), _] => key.qself.is_none() && key.path.segments.last().is_some_and(|s| s.ident == "String"),
+        _ => false,
+    }
+}
@@ -0,0 +1,557 @@
+use crate::{DeezError, Number, Timestamp};
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::hash::{BuildHasher, Hash};
+use std::marker::PhantomData;
+use std::time::SystemTime;
+
+/// Types that can be stored as a DynamoDB attribute.
+///
+/// Implemented for all supported field types and derived along with
+/// [`FromAttributeValue`] by `Deez`. Implement both traits for your own types
+/// to use them as fields or in composite keys:
+///
+/// ```
+/// # use aws_sdk_dynamodb::types::AttributeValue;
+/// # use deez::*;
+/// struct UserId(String);
+///
+/// impl IntoAttributeValue for UserId {
+///     fn to_av(&self) -> AttributeValue {
+///         AttributeValue::S(self.0.clone())
+///     }
+/// }
+///
+/// impl FromAttributeValue for UserId {
+///     fn from_av(av: &AttributeValue) -> Result<UserId, DeezError> {
+///         String::from_av(av).map(UserId)
+///     }
+/// }
+/// ```
+pub trait IntoAttributeValue {
+    fn to_av(&self) -> AttributeValue;
+
+    fn into_av(self) -> AttributeValue
+    where
+        Self: Sized,
+    {
+        self.to_av()
+    }
+
+    /// Text used when the value is part of a composite key. Defaults to the
+    /// value of an `S`, `N` or `BOOL` attribute and the hex encoding of a `B`.
+    ///
+    /// # Panics
+    ///
+    /// The default implementation panics for other attribute types.
+    fn to_key_part(&self) -> String {
+        match self.to_av() {
+            AttributeValue::S(s) | AttributeValue::N(s) => s,
+            AttributeValue::Bool(b) => b.to_string(),
+            AttributeValue::B(b) => b.as_ref().iter().map(|b| format!("{:02x}", b)).collect(),
+            av => panic!("{:?} can not be used in a composite key", av),
+        }
+    }
+}
+
+/// Types that can be read from a DynamoDB attribute.
+///
+/// Errors are returned without the entity and field, which are added by the
+/// derived conversions.
+pub trait FromAttributeValue: Sized {
+    fn from_av(av: &AttributeValue) -> Result<Self, DeezError>;
+
+    /// The value of an absent attribute, or `None` if the attribute is
+    /// required. Collections are empty, since empty sets are left out of
+    /// items.
+    fn from_absent() -> Option<Self> {
+        None
+    }
+
+    /// Reads a value from its text in a composite key, the inverse of
+    /// [`IntoAttributeValue::to_key_part`]. Defaults to reading the text as an
+    /// `S`, `N`, `BOOL` or hex encoded `B` attribute, in that order.
+    fn from_key_part(s: &str) -> Result<Self, DeezError> {
+        Self::from_av(&AttributeValue::S(s.to_string()))
+            .or_else(|_| Self::from_av(&AttributeValue::N(s.to_string())))
+            .or_else(|e| match s.parse::<bool>() {
+                Ok(b) => Self::from_av(&AttributeValue::Bool(b)).map_err(|_| e),
+                Err(_) => Err(e),
+            })
+            .or_else(|e| match decode_hex(s) {
+                Some(b) => Self::from_av(&AttributeValue::B(Blob::new(b))).map_err(|_| e),
+                None => Err(e),
+            })
+    }
+}
+
+/// Types that can be stored in a DynamoDB string, number or binary set, used by
+/// `HashSet` and `BTreeSet` fields and `#[deez_vec(dynamo_type = "set")]`.
+pub trait SetAttributeValue: Sized {
+    fn to_set<'a, I>(values: I) -> AttributeValue
+    where
+        I: IntoIterator<Item = &'a Self>,
+        Self: 'a;
+
+    fn from_set(av: &AttributeValue) -> Result<Vec<Self>, DeezError>;
+}
+
+fn unexpected(expected: &'static str) -> DeezError {
+    DeezError::UnexpectedVariant {
+        entity: String::new(),
+        field: String::new(),
+        expected,
+    }
+}
+
+/// Decodes the hex encoding of `to_key_part`, failing for odd lengths.
+pub(crate) fn decode_hex(s: &str) -> Option<Vec<u8>> {
+    (0..s.len())
+        .step_by(2)
+        .map(|i| s.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok()))
+        .collect()
+}
+
+/// Checks that the numbers in `av` are valid DynamoDB numbers, which
+/// `IntoAttributeValue` can not report, e.g. `i128` and `u128` values of more
+/// than 38 digits. Used by the generated `try_to_item`.
+#[doc(hidden)]
+pub fn check_numbers(av: &AttributeValue) -> Result<(), DeezError> {
+    let check = |n: &String| match Number::try_from(n.as_str()) {
+        Ok(_) => Ok(()),
+        Err(DeezError::NumberValidation { value, reason }) => Err(DeezError::InvalidValue {
+            entity: String::new(),
+            field: String::new(),
+            value,
+            reason: reason.to_string(),
+        }),
+        Err(e) => Err(e),
+    };
+    match av {
+        AttributeValue::N(n) => check(n),
+        AttributeValue::Ns(ns) => ns.iter().try_for_each(check),
+        AttributeValue::L(l) => l.iter().try_for_each(check_numbers),
+        AttributeValue::M(m) => m.values().try_for_each(check_numbers),
+        _ => Ok(()),
+    }
+}
+
+/// Gives the `Default` of an entity that implements it, for the `deez_ignore`
+/// fields of an item being read. Called as
+/// `(&EntityDefault::<T>(PhantomData)).entity_default()`, which resolves to
+/// `WithoutDefault` for entities without `Default`.
+#[doc(hidden)]
+pub struct EntityDefault<T>(pub PhantomData<T>);
+
+#[doc(hidden)]
+pub trait WithDefault<T> {
+    fn entity_default(&self) -> Option<T>;
+}
+
+impl<T: Default> WithDefault<T> for EntityDefault<T> {
+    fn entity_default(&self) -> Option<T> {
+        Some(T::default())
+    }
+}
+
+#[doc(hidden)]
+pub trait WithoutDefault<T> {
+    fn entity_default(&self) -> Option<T>;
+}
+
+impl<T> WithoutDefault<T> for &EntityDefault<T> {
+    fn entity_default(&self) -> Option<T> {
+        None
+    }
+}
+
+fn invalid_number(n: &str) -> DeezError {
+    DeezError::InvalidNumber {
+        entity: String::new(),
+        field: String::new(),
+        value: n.to_string(),
+    }
+}
+
+impl IntoAttributeValue for AttributeValue {
+    fn to_av(&self) -> AttributeValue {
+        self.clone()
+    }
+
+    fn into_av(self) -> AttributeValue {
+        self
+    }
+}
+
+impl FromAttributeValue for AttributeValue {
+    fn from_av(av: &AttributeValue) -> Result<AttributeValue, DeezError> {
+        Ok(av.clone())
+    }
+}
+
+impl IntoAttributeValue for String {
+    fn to_av(&self) -> AttributeValue {
+        AttributeValue::S(self.clone())
+    }
+
+    fn into_av(self) -> AttributeValue {
+        AttributeValue::S(self)
+    }
+}
+
+impl FromAttributeValue for String {
+    fn from_av(av: &AttributeValue) -> Result<String, DeezError> {
+        match av {
+            AttributeValue::S(s) => Ok(s.clone()),
+            _ => Err(unexpected("S")),
+        }
+    }
+}
+
+impl SetAttributeValue for String {
+    fn to_set<'a, I>(values: I) -> AttributeValue
+    where
+        I: IntoIterator<Item = &'a String>,
+    {
+        AttributeValue::Ss(values.into_iter().cloned().collect())
+    }
+
+    fn from_set(av: &AttributeValue) -> Result<Vec<String>, DeezError> {
+        match av {
+            AttributeValue::Ss(ss) => Ok(ss.clone()),
+            _ => Err(unexpected("Ss")),
+        }
+    }
+}
+
+impl IntoAttributeValue for bool {
+    fn to_av(&self) -> AttributeValue {
+        AttributeValue::Bool(*self)
+    }
+}
+
+impl FromAttributeValue for bool {
+    fn from_av(av: &AttributeValue) -> Result<bool, DeezError> {
+        match av {
+            AttributeValue::Bool(b) => Ok(*b),
+            _ => Err(unexpected("Bool")),
+        }
+    }
+}
+
+impl IntoAttributeValue for Blob {
+    fn to_av(&self) -> AttributeValue {
+        AttributeValue::B(self.clone())
+    }
+
+    fn into_av(self) -> AttributeValue {
+        AttributeValue::B(self)
+    }
+}
+
+impl FromAttributeValue for Blob {
+    fn from_av(av: &AttributeValue) -> Result<Blob, DeezError> {
+        match av {
+            AttributeValue::B(b) => Ok(b.clone()),
+            _ => Err(unexpected("B")),
+        }
+    }
+}
+
+impl SetAttributeValue for Blob {
+    fn to_set<'a, I>(values: I) -> AttributeValue
+    where
+        I: IntoIterator<Item = &'a Blob>,
+    {
+        AttributeValue::Bs(values.into_iter().cloned().collect())
+    }
+
+    fn from_set(av: &AttributeValue) -> Result<Vec<Blob>, DeezError> {
+        match av {
+            AttributeValue::Bs(bs) => Ok(bs.clone()),
+            _ => Err(unexpected("Bs")),
+        }
+    }
+}
+
+/// Implements the conversion traits for a type stored as `N`, given a function
+/// parsing the number.
+macro_rules! number_attribute_value {
+    ($t:ty, $parse:expr) => {
+        impl IntoAttributeValue for $t {
+            fn to_av(&self) -> AttributeValue {
+                AttributeValue::N(self.to_string())
+            }
+        }
+
+        impl FromAttributeValue for $t {
+            fn from_av(av: &AttributeValue) -> Result<$t, DeezError> {
+                match av {
+                    AttributeValue::N(n) => ($parse)(n),
+                    _ => Err(unexpected("N")),
+                }
+            }
+        }
+
+        impl SetAttributeValue for $t {
+            fn to_set<'a, I>(values: I) -> AttributeValue
+            where
+                I: IntoIterator<Item = &'a $t>,
+            {
+                AttributeValue::Ns(values.into_iter().map(|n| n.to_string()).collect())
+            }
+
+            fn from_set(av: &AttributeValue) -> Result<Vec<$t>, DeezError> {
+                match av {
+                    AttributeValue::Ns(ns) => ns.iter().map(|n| ($parse)(n)).collect(),
+                    _ => Err(unexpected("Ns")),
+                }
+            }
+        }
+    };
+}
+
+macro_rules! int_attribute_value {
+    ($($t:ident),*) => {
+        $(
+            number_attribute_value!($t, |n: &String| {
+                n.parse::<$t>().map_err(|e| match e.kind() {
+                    std::num::IntErrorKind::PosOverflow | std::num::IntErrorKind::NegOverflow => {
+                        DeezError::NumberOverflow {
+                            entity: String::new(),
+                            field: String::new(),
+                            value: n.clone(),
+                            ty: stringify!($t),
+                        }
+                    }
+                    _ => invalid_number(n),
+                })
+            });
+        )*
+    };
+}
+
+int_attribute_value!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+number_attribute_value!(f64, |n: &String| n.parse::<f64>().map_err(|_| invalid_number(n)));
+
+number_attribute_value!(Number, |n: &String| {
+    Number::try_from(n.as_str()).map_err(|_| invalid_number(n))
+});
+
+#[cfg(feature = "rust_decimal")]
+number_attribute_value!(rust_decimal::Decimal, |n: &String| {
+    Number::try_from(n.as_str())
+        .and_then(rust_decimal::Decimal::try_from)
+        .map_err(|_| invalid_number(n))
+});
+
+/// Timestamps are stored as ISO-8601 strings unless the field has a
+/// `#[deez_time(format = "...")]` attribute.
+macro_rules! timestamp_attribute_value {
+    ($t:ty) => {
+        impl IntoAttributeValue for $t {
+            fn to_av(&self) -> AttributeValue {
+                AttributeValue::S(self.to_iso8601())
+            }
+        }
+
+        impl FromAttributeValue for $t {
+            fn from_av(av: &AttributeValue) -> Result<$t, DeezError> {
+                match av {
+                    AttributeValue::S(s) => parse_timestamp(s),
+                    _ => Err(unexpected("S")),
+                }
+            }
+        }
+
+        impl SetAttributeValue for $t {
+            fn to_set<'a, I>(values: I) -> AttributeValue
+            where
+                I: IntoIterator<Item = &'a $t>,
+            {
+                AttributeValue::Ss(values.into_iter().map(|t| t.to_iso8601()).collect())
+            }
+
+            fn from_set(av: &AttributeValue) -> Result<Vec<$t>, DeezError> {
+                match av {
+                    AttributeValue::Ss(ss) => ss.iter().map(|s| parse_timestamp(s)).collect(),
+                    _ => Err(unexpected("Ss")),
+                }
+            }
+        }
+    };
+}
+
+fn parse_timestamp<T: Timestamp>(s: &str) -> Result<T, DeezError> {
+    T::from_iso8601(s).ok_or_else(|| DeezError::InvalidTimestamp {
+        entity: String::new(),
+        field: String::new(),
+        value: s.to_string(),
+    })
+}
+
+timestamp_attribute_value!(SystemTime);
+
+#[cfg(feature = "chrono")]
+timestamp_attribute_value!(chrono::DateTime<chrono::Utc>);
+
+#[cfg(feature = "chrono")]
+timestamp_attribute_value!(chrono::NaiveDate);
+
+impl<T: IntoAttributeValue> IntoAttributeValue for Box<T> {
+    fn to_av(&self) -> AttributeValue {
+        (**self).to_av()
+    }
+
+    fn into_av(self) -> AttributeValue {
+        (*self).into_av()
+    }
+
+    fn to_key_part(&self) -> String {
+        (**self).to_key_part()
+    }
+}
+
+impl<T: FromAttributeValue> FromAttributeValue for Box<T> {
+    fn from_av(av: &AttributeValue) -> Result<Box<T>, DeezError> {
+        T::from_av(av).map(Box::new)
+    }
+
+    fn from_absent() -> Option<Box<T>> {
+        T::from_absent().map(Box::new)
+    }
+}
+
+impl<T: IntoAttributeValue> IntoAttributeValue for Option<T> {
+    fn to_av(&self) -> AttributeValue {
+        match self {
+            Some(v) => v.to_av(),
+            None => AttributeValue::Null(true),
+        }
+    }
+
+    fn into_av(self) -> AttributeValue {
+        match self {
+            Some(v) => v.into_av(),
+            None => AttributeValue::Null(true),
+        }
+    }
+}
+
+impl<T: FromAttributeValue> FromAttributeValue for Option<T> {
+    fn from_av(av: &AttributeValue) -> Result<Option<T>, DeezError> {
+        match av {
+            AttributeValue::Null(_) => Ok(None),
+            av => T::from_av(av).map(Some),
+        }
+    }
+}
+
+impl<T: IntoAttributeValue> IntoAttributeValue for Vec<T> {
+    fn to_av(&self) -> AttributeValue {
+        AttributeValue::L(self.iter().map(T::to_av).collect())
+    }
+
+    fn into_av(self) -> AttributeValue {
+        AttributeValue::L(self.into_iter().map(T::into_av).collect())
+    }
+}
+
+impl<T: FromAttributeValue> FromAttributeValue for Vec<T> {
+    fn from_av(av: &AttributeValue) -> Result<Vec<T>, DeezError> {
+        match av {
+            AttributeValue::L(l) => l.iter().map(T::from_av).collect(),
+            _ => Err(unexpected("L")),
+        }
+    }
+
+    fn from_absent() -> Option<Vec<T>> {
+        Some(Default::default())
+    }
+}
+
+impl<T: IntoAttributeValue, S> IntoAttributeValue for HashMap<String, T, S> {
+    fn to_av(&self) -> AttributeValue {
+        AttributeValue::M(self.iter().map(|(k, v)| (k.clone(), v.to_av())).collect())
+    }
+
+    fn into_av(self) -> AttributeValue {
+        AttributeValue::M(self.into_iter().map(|(k, v)| (k, v.into_av())).collect())
+    }
+}
+
+impl<T: FromAttributeValue, S: BuildHasher + Default> FromAttributeValue for HashMap<String, T, S> {
+    fn from_av(av: &AttributeValue) -> Result<HashMap<String, T, S>, DeezError> {
+        match av {
+            AttributeValue::M(m) => m
+                .iter()
+                .map(|(k, v)| T::from_av(v).map(|v| (k.clone(), v)))
+                .collect(),
+            _ => Err(unexpected("M")),
+        }
+    }
+
+    fn from_absent() -> Option<HashMap<String, T, S>> {
+        Some(Default::default())
+    }
+}
+
+impl<T: IntoAttributeValue> IntoAttributeValue for BTreeMap<String, T> {
+    fn to_av(&self) -> AttributeValue {
+        AttributeValue::M(self.iter().map(|(k, v)| (k.clone(), v.to_av())).collect())
+    }
+
+    fn into_av(self) -> AttributeValue {
+        AttributeValue::M(self.into_iter().map(|(k, v)| (k, v.into_av())).collect())
+    }
+}
+
+impl<T: FromAttributeValue> FromAttributeValue for BTreeMap<String, T> {
+    fn from_av(av: &AttributeValue) -> Result<BTreeMap<String, T>, DeezError> {
+        match av {
+            AttributeValue::M(m) => m
+                .iter()
+                .map(|(k, v)| T::from_av(v).map(|v| (k.clone(), v)))
+                .collect(),
+            _ => Err(unexpected("M")),
+        }
+    }
+
+    fn from_absent() -> Option<BTreeMap<String, T>> {
+        Some(Default::default())
+    }
+}
+
+impl<T: SetAttributeValue, S> IntoAttributeValue for HashSet<T, S> {
+    fn to_av(&self) -> AttributeValue {
+        T::to_set(self)
+    }
+}
+
+impl<T: SetAttributeValue + Eq + Hash, S: BuildHasher + Default> FromAttributeValue for HashSet<T, S> {
+    fn from_av(av: &AttributeValue) -> Result<HashSet<T, S>, DeezError> {
+        T::from_set(av).map(|v| v.into_iter().collect())
+    }
+
+    fn from_absent() -> Option<HashSet<T, S>> {
+        Some(Default::default())
+    }
+}
+
+impl<T: SetAttributeValue> IntoAttributeValue for BTreeSet<T> {
+    fn to_av(&self) -> AttributeValue {
+        T::to_set(self)
+    }
+}
+
+impl<T: SetAttributeValue + Ord> FromAttributeValue for BTreeSet<T> {
+    fn from_av(av: &AttributeValue) -> Result<BTreeSet<T>, DeezError> {
+        T::from_set(av).map(|v| v.into_iter().collect())
+    }
+
+    fn from_absent() -> Option<BTreeSet<T>> {
+        Some(Default::default())
+    }
+}
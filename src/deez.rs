@@ -1,26 +1,167 @@
+use crate::convert::decode_hex;
+use crate::DeezError;
+use aws_sdk_dynamodb::primitives::Blob;
 use aws_sdk_dynamodb::types::AttributeValue;
+use std::collections::HashMap;
 
 #[derive(Debug)]
 pub struct IndexKeys {
     pub hash: IndexKey,
-    pub range: IndexKey,
+    /// `None` for indexes with only a hash key.
+    pub range: Option<IndexKey>,
+}
+
+impl IndexKeys {
+    /// The key attributes of the item, as used by `GetItem` or `DeleteItem`.
+    pub fn key(&self) -> HashMap<String, AttributeValue> {
+        let mut m = HashMap::from([(self.hash.field(), self.hash.av())]);
+        if let Some(range) = &self.range {
+            m.insert(range.field(), range.av());
+        }
+        m
+    }
+
+    /// Whether both the hash and range keys are complete.
+    pub fn is_complete(&self) -> bool {
+        self.hash.is_complete() && self.range.iter().all(|r| r.is_complete())
+    }
+
+    /// The key condition of a query on the hash key, and on the range key if
+    /// `range` is given. Values keep the type of their key, so number keys
+    /// compare numerically.
+    pub fn key_condition(&self, range: Option<RangeCondition>) -> Result<KeyCondition, DeezError> {
+        let mut condition = KeyCondition {
+            expression: "#pk = :pk".to_string(),
+            names: HashMap::from([("#pk".to_string(), self.hash.field())]),
+            values: HashMap::from([(":pk".to_string(), self.hash.try_av()?)]),
+        };
+        let (key, range) = match (&self.range, range) {
+            (_, None) => return Ok(condition),
+            (Some(key), Some(range)) => (key, range),
+            (None, Some(_)) => {
+                return Err(DeezError::InvalidKey {
+                    key: self.hash.composite.clone(),
+                    reason: "the index has no range key".to_string(),
+                })
+            }
+        };
+        let mut values = vec![key];
+        let expression = match &range {
+            RangeCondition::Eq => "#sk = :sk",
+            RangeCondition::Lt => "#sk < :sk",
+            RangeCondition::Le => "#sk <= :sk",
+            RangeCondition::Gt => "#sk > :sk",
+            RangeCondition::Ge => "#sk >= :sk",
+            RangeCondition::BeginsWith if key.key_type == KeyType::Number => {
+                return Err(DeezError::InvalidKey {
+                    key: key.composite.clone(),
+                    reason: "`begins_with` can not be used with number keys".to_string(),
+                })
+            }
+            RangeCondition::BeginsWith => "begins_with(#sk, :sk)",
+            RangeCondition::Between(upper) => {
+                values.push(upper);
+                "#sk BETWEEN :sk AND :sk2"
+            }
+        };
+        condition.expression = format!("{} AND {}", condition.expression, expression);
+        condition.names.insert("#sk".to_string(), key.field());
+        for (name, key) in [":sk", ":sk2"].into_iter().zip(values) {
+            // only string keys can be partial, a number or binary key is a single value
+            if key.key_type != KeyType::String && !key.is_complete() {
+                return Err(DeezError::InvalidKey {
+                    key: key.composite.clone(),
+                    reason: "number and binary keys must be complete".to_string(),
+                });
+            }
+            condition.values.insert(name.to_string(), key.try_av()?);
+        }
+        Ok(condition)
+    }
+}
+
+/// Comparison of the range key in a key condition built by
+/// `IndexKeys::key_condition`.
+#[derive(Debug)]
+pub enum RangeCondition {
+    Eq,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// Not allowed for number keys.
+    BeginsWith,
+    /// Between the range key and the given upper bound, inclusive.
+    Between(IndexKey),
+}
+
+/// The parts of a query's key condition, for `key_condition_expression`,
+/// `set_expression_attribute_names` and `set_expression_attribute_values`.
+#[derive(Debug)]
+pub struct KeyCondition {
+    pub expression: String,
+    pub names: HashMap<String, String>,
+    pub values: HashMap<String, AttributeValue>,
 }
 
 #[derive(Debug, Default)]
 pub struct IndexKey {
     pub field: String,
+    /// The composed key, or the value of a number key and the hex encoding of
+    /// a binary key.
     pub composite: String,
+    /// Whether every composite of the key was set.
+    pub complete: bool,
+    pub key_type: KeyType,
 }
 
 impl IndexKey {
+    /// Returns `false` when a composite was `None`, in which case `composite`
+    /// only holds the prefix up to the missing value.
+    pub fn is_complete(&self) -> bool {
+        self.complete
+    }
     pub fn field(&self) -> String {
         self.field.clone()
     }
+    /// The key as an attribute value of its `key_type`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a binary key's `composite` is not valid hex, which can only
+    /// happen when it was set by hand. Use `try_av` for such keys.
     pub fn av(&self) -> AttributeValue {
-        AttributeValue::S(self.composite.clone())
+        self.try_av().unwrap_or_else(|e| panic!("{}", e))
+    }
+    /// Like `av`, but returns an error when a binary key's `composite` is not
+    /// valid hex.
+    pub fn try_av(&self) -> Result<AttributeValue, DeezError> {
+        Ok(match self.key_type {
+            KeyType::String => AttributeValue::S(self.composite.clone()),
+            KeyType::Number => AttributeValue::N(self.composite.clone()),
+            KeyType::Binary => match decode_hex(&self.composite) {
+                Some(bytes) => AttributeValue::B(Blob::new(bytes)),
+                None => {
+                    return Err(DeezError::InvalidKey {
+                        key: self.composite.clone(),
+                        reason: "binary key is not valid hex".to_string(),
+                    })
+                }
+            },
+        })
     }
 }
 
+/// DynamoDB type of a key attribute, declared with the `*_type` options of
+/// `deez_schema` and `deez_index`.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum KeyType {
+    #[default]
+    String,
+    Number,
+    Binary,
+}
+
 #[derive(Eq, Hash, PartialEq, Debug)]
 pub enum Key {
     Hash,
@@ -30,8 +171,9 @@ pub enum Key {
 #[cfg(test)]
 mod tests {
     use crate::mocks::mocks::*;
+    use crate::{DeezError, FromAttributeValue, IntoAttributeValue, Key, KeyType, Number, RangeCondition};
     use aws_sdk_dynamodb::types::AttributeValue;
-    use std::collections::HashMap;
+    use std::collections::{HashMap, HashSet};
 
     #[test]
     fn all_types() {
@@ -43,7 +185,7 @@ mod tests {
         let b: &HashMap<String, AttributeValue> = &a.into();
         println!("{:#?}", b);
 
-        let c: Buss = b.into();
+        let c: Buss = b.try_into().unwrap();
         println!("{:#?}", c);
 
         // todo: assert
@@ -55,6 +197,245 @@ mod tests {
         assert_eq!(Task::gsi2_name(), "task_gsi2");
     }
 
+    #[test]
+    fn named_indexes() {
+        assert_eq!(Assignment::by_project_name(), "task_gsi1");
+        assert_eq!(Assignment::by_employee_name(), "task_gsi2");
+
+        let a = Assignment {
+            assignment_id: Some("a1".to_string()),
+            project: Some("deez".to_string()),
+            employee: Some("ddp".to_string()),
+        };
+
+        let keys = a.by_project_keys();
+        assert_eq!(keys.hash.field(), "gsi1pk");
+        assert_eq!(keys.hash.composite, "$TaskService#Assignment#project_deez");
+        assert_eq!(keys.range.as_ref().unwrap().field(), "gsi1sk");
+        assert_eq!(keys.range.as_ref().unwrap().composite, "$Assignment#employee_ddp#assignment_id_a1");
+
+        let m: HashMap<String, AttributeValue> = a.into();
+        assert_eq!(
+            m["gsi2pk"],
+            AttributeValue::S("$TaskService#Assignment#employee_ddp".to_string())
+        );
+        assert_eq!(
+            m["gsi2sk"],
+            AttributeValue::S("$Assignment#project_deez".to_string())
+        );
+        assert_eq!(
+            m["pk"],
+            AttributeValue::S("$TaskService#Assignment#assignment_id_a1".to_string())
+        );
+    }
+
+    #[test]
+    fn local_indexes() {
+        assert_eq!(Audit::by_actor_name(), "lsi1");
+
+        let a = Audit {
+            task_id: Some("t1".to_string()),
+            audit_id: Some("a1".to_string()),
+            actor: Some("ddp".to_string()),
+            note: None,
+        };
+
+        let keys = a.by_actor_keys();
+        assert_eq!(keys.hash.field(), "pk");
+        assert_eq!(keys.hash.composite, "$TaskService#Audit#task_id_t1");
+        assert_eq!(keys.range.as_ref().unwrap().field(), "lsi1sk");
+        assert_eq!(keys.range.as_ref().unwrap().composite, "$Audit#actor_ddp#audit_id_a1");
+
+        let m: HashMap<String, AttributeValue> = a.into();
+        assert_eq!(
+            m["pk"],
+            AttributeValue::S("$TaskService#Audit#task_id_t1".to_string())
+        );
+        assert_eq!(
+            m["lsi1sk"],
+            AttributeValue::S("$Audit#actor_ddp#audit_id_a1".to_string())
+        );
+    }
+
+    #[test]
+    fn hash_only() {
+        let a = Account {
+            account_id: Some("a1".to_string()),
+            email: Some("ddp@deez.nuts".to_string()),
+            ..Default::default()
+        };
+
+        let keys = a.primary_keys();
+        assert!(keys.range.is_none());
+        assert_eq!(
+            keys.key(),
+            HashMap::from([(
+                "pk".to_string(),
+                AttributeValue::S("$AccountService#Account#account_id_a1".to_string())
+            )])
+        );
+        assert!(a.gsi1_keys().range.is_none());
+        let range = a.primary_key(Key::Range);
+        assert_eq!(range.field(), "");
+        assert_eq!(range.composite, "");
+        assert!(!range.is_complete());
+
+        let m: HashMap<String, AttributeValue> = a.into();
+        assert_eq!(
+            m["gsi1pk"],
+            AttributeValue::S("$AccountService#Account#email_ddp@deez.nuts".to_string())
+        );
+        assert!(!m.contains_key("sk"));
+        assert!(!m.contains_key("gsi1sk"));
+    }
+
+    #[test]
+    fn sparse_indexes() {
+        let a = Account {
+            account_id: Some("a1".to_string()),
+            ..Default::default()
+        };
+
+        let keys = a.by_referrer_keys();
+        assert!(!keys.hash.is_complete());
+        assert!(!keys.is_complete());
+        assert!(a.primary_keys().is_complete());
+
+        // only the sparse index is omitted
+        let m: HashMap<String, AttributeValue> = a.into();
+        assert!(m.contains_key("gsi1pk"));
+        assert!(!m.contains_key("gsi2pk"));
+        assert!(!m.contains_key("gsi2sk"));
+
+        let a = Account {
+            account_id: Some("a1".to_string()),
+            referrer: Some("a0".to_string()),
+            ..Default::default()
+        };
+        assert!(a.by_referrer_keys().is_complete());
+
+        let m: HashMap<String, AttributeValue> = a.into();
+        assert_eq!(
+            m["gsi2pk"],
+            AttributeValue::S("$AccountService#Account#referrer_a0".to_string())
+        );
+        assert_eq!(
+            m["gsi2sk"],
+            AttributeValue::S("$Account#account_id_a1".to_string())
+        );
+    }
+
+    #[test]
+    fn skip_none() {
+        let a = Account {
+            account_id: Some("a1".to_string()),
+            ..Default::default()
+        };
+        let m: HashMap<String, AttributeValue> = a.into();
+        assert!(m.contains_key("account_id"));
+        assert!(!m.contains_key("email"));
+        assert!(!m.contains_key("nickname"));
+
+        let b = Account::try_from(&m).unwrap();
+        assert_eq!(b.account_id, Some("a1".to_string()));
+        assert_eq!(b.nickname, None);
+
+        let a = Audit {
+            task_id: Some("t1".to_string()),
+            ..Default::default()
+        };
+        let m: HashMap<String, AttributeValue> = a.into();
+        assert_eq!(m["actor"], AttributeValue::Null(true));
+        assert!(!m.contains_key("note"));
+        assert_eq!(Audit::try_from(&m).unwrap().note, None);
+    }
+
+    #[test]
+    fn empty_collections() {
+        let a = Basket {
+            numbers: Some(HashSet::new()),
+            prices: Some(vec![]),
+            levels: Some(Default::default()),
+            ..Default::default()
+        };
+        let m: HashMap<String, AttributeValue> = a.into();
+        for k in [
+            "names", "counts", "blobs", "numbers", "tags", "prices", "items", "qualified", "levels", "aliased",
+        ] {
+            assert!(!m.contains_key(k), "{} should be omitted", k);
+        }
+        assert_eq!(m["notes"], AttributeValue::L(vec![]));
+        assert_eq!(m["extras"], AttributeValue::Null(true));
+
+        let b = Basket::try_from(&m).unwrap();
+        assert!(b.names.is_empty());
+        assert!(b.counts.is_empty());
+        assert!(b.blobs.is_empty());
+        assert!(b.numbers.is_none());
+        assert!(b.tags.is_empty());
+        assert!(b.prices.is_none());
+        assert!(b.items.is_empty());
+        assert!(b.extras.is_empty());
+        assert!(b.qualified.is_empty());
+        assert!(b.levels.is_none());
+        assert!(b.aliased.is_empty());
+
+        // absent lists are read as empty, whatever the path of their type
+        let mut m = m;
+        m.remove("notes");
+        m.insert("lines".to_string(), AttributeValue::Null(true));
+        let b = Basket::try_from(&m).unwrap();
+        assert!(b.notes.is_empty());
+        assert!(b.lines.is_empty());
+
+        let a = Basket {
+            names: HashSet::from(["deez".to_string()]),
+            items: vec!["nuts".to_string()],
+            ..Default::default()
+        };
+        let m: HashMap<String, AttributeValue> = a.into();
+        assert_eq!(m["names"], AttributeValue::Ss(vec!["deez".to_string()]));
+        assert_eq!(m["items"], AttributeValue::L(vec![AttributeValue::S("nuts".to_string())]));
+    }
+
+    #[test]
+    fn defaults() {
+        let a = Legacy {
+            version: 2,
+            region: "eu-west-1".to_string(),
+            ..Default::default()
+        };
+        let mut m: HashMap<String, AttributeValue> = a.into();
+        let b = Legacy::try_from(&m).unwrap();
+        assert_eq!(b.version, 2);
+        assert_eq!(b.region, "eu-west-1");
+
+        // items written before the fields were added
+        m.remove("version");
+        m.remove("region");
+        let b = Legacy::try_from(&m).unwrap();
+        assert_eq!(b.version, 0);
+        assert_eq!(b.region, "us-east-1");
+    }
+
+    #[test]
+    fn without_default() {
+        let a = Session {
+            session_id: Some("s1".to_string()),
+            user_id: UserId(7),
+            cache: vec!["deez".to_string()],
+            region: "eu-west-1".to_string(),
+        };
+        let m: HashMap<String, AttributeValue> = a.into();
+        assert!(!m.contains_key("cache"));
+
+        let b = Session::try_from(&m).unwrap();
+        assert_eq!(b.session_id, Some("s1".to_string()));
+        assert_eq!(b.user_id, UserId(7));
+        assert!(b.cache.is_empty());
+        assert_eq!(b.region, "us-east-1");
+    }
+
     #[test]
     fn to_from() {
         {
@@ -94,7 +475,7 @@ mod tests {
                 AttributeValue::S("$Task#project_foo_project#task_id_1a2b3c4d".to_string())
             );
 
-            let c: Task = b.into();
+            let c: Task = b.try_into().unwrap();
             println!("{:#?}", c);
 
             assert_eq!(c.task_id, Some("1a2b3c4d".to_string()));
@@ -105,6 +486,125 @@ mod tests {
         }
     }
 
+    #[test]
+    fn to_item() {
+        let task = Task::default();
+        let item = task.to_item();
+        assert_eq!(item, HashMap::from(&task));
+        assert_eq!(item, HashMap::from(task));
+        let mut keys: Vec<&str> = item.keys().map(String::as_str).collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            ["description", "employee", "gsi1pk", "gsi1sk", "gsi2pk", "gsi2sk", "pk", "project", "sk", "task_id"]
+        );
+
+        // sets are converted from the reference as well
+        let b = Basket {
+            names: HashSet::from(["a".to_string(), "b".to_string()]),
+            counts: [3, 1].into(),
+            numbers: Some(HashSet::from([Number::from(7)])),
+            tags: vec!["x".to_string()],
+            prices: Some(vec![1.5]),
+            ..Default::default()
+        };
+        assert_eq!(b.to_item(), HashMap::from(b));
+        let r = Reminder::default();
+        assert_eq!(r.to_item(), HashMap::from(r));
+
+        let a = Audit {
+            task_id: Some("t1".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(a.to_item(), HashMap::from(a));
+    }
+
+    #[test]
+    fn required_keys() {
+        let mut a = Membership {
+            org_id: "deez".to_string(),
+            status: Status::InProgress,
+            seat: 3,
+            user_id: None,
+        };
+
+        let keys = a.primary_keys();
+        assert_eq!(keys.hash.composite, "$TaskService#Membership#org_id_deez");
+        assert_eq!(
+            keys.range.as_ref().unwrap().composite,
+            "$Membership#status_in_progress#seat_3"
+        );
+        assert!(!keys.is_complete());
+
+        a.user_id = Some(UserId(7));
+        let keys = a.primary_keys();
+        assert_eq!(
+            keys.range.as_ref().unwrap().composite,
+            "$Membership#status_in_progress#seat_3#user_id_user-7"
+        );
+        assert!(keys.is_complete());
+    }
+
+    #[test]
+    fn templates() {
+        let mut a = OrgUser {
+            org_id: "acme".to_string(),
+            user_id: None,
+            email: Some("ddp@deez.nuts".to_string()),
+            name: Some("ddp".to_string()),
+        };
+
+        let keys = a.primary_keys();
+        assert_eq!(keys.hash.field(), "pk");
+        assert_eq!(keys.hash.composite, "ORG#acme");
+        assert_eq!(keys.range.as_ref().unwrap().composite, "USER#");
+        assert!(!keys.is_complete());
+
+        a.user_id = Some(UserId(7));
+        let m = a.to_item();
+        assert_eq!(m["pk"], AttributeValue::S("ORG#acme".to_string()));
+        assert_eq!(m["sk"], AttributeValue::S("USER#user-7#PROFILE".to_string()));
+        assert_eq!(m["gsi1pk"], AttributeValue::S("EMAIL#ddp@deez.nuts".to_string()));
+        // keys without a template keep the default format
+        assert_eq!(m["gsi1sk"], AttributeValue::S("$OrgUser#name_ddp".to_string()));
+
+        // values are escaped against the template text following them
+        a.org_id = "a-EMAIL-b".to_string();
+        a.email = Some("c".to_string());
+        let keys = a.by_org_email_keys();
+        assert_eq!(keys.hash.composite, "ORG-a\\-EMAIL\\-b-EMAIL-c");
+        let parts = OrgUser::parse_by_org_email_key(&keys.hash.composite).unwrap();
+        assert_eq!(parts.org_id.as_deref(), Some("a-EMAIL-b"));
+        assert_eq!(parts.email.as_deref(), Some("c"));
+    }
+
+    #[test]
+    fn key_format() {
+        let a = Contact {
+            email: "DDP@Deez.Nuts".to_string(),
+            list: Some("News".to_string()),
+            name: Some("ddp".to_string()),
+        };
+        let m = a.to_item();
+        assert_eq!(
+            m["pk"],
+            AttributeValue::S("$contactservice|contact|email:ddp@deez.nuts".to_string())
+        );
+        assert_eq!(m["sk"], AttributeValue::S("$contact|list:news|name:ddp".to_string()));
+        // template literals are kept as they are
+        assert_eq!(m["gsi1pk"], AttributeValue::S("EMAIL#ddp@deez.nuts".to_string()));
+        assert_eq!(m["email"], AttributeValue::S("DDP@Deez.Nuts".to_string()));
+
+        let a = Tag {
+            name: "rust".to_string(),
+            owner: Some("ddp".to_string()),
+            scope: None,
+        };
+        let keys = a.primary_keys();
+        assert_eq!(keys.hash.composite, "NAME_RUST");
+        assert_eq!(keys.range.as_ref().unwrap().composite, "OWNER_DDP");
+    }
+
     #[test]
     fn partial_keys() {
         let mut task = Task {
@@ -117,18 +617,722 @@ mod tests {
         };
 
         {
-            let map: HashMap<String, AttributeValue> = task.clone().into();
+            let map = task.to_item();
             // println!("{:#?}", map);
             assert_eq!(map["sk"], AttributeValue::S("$Task".to_string()));
         }
 
         {
             task.employee = Some("e42069".to_string());
-            let map: HashMap<String, AttributeValue> = task.clone().into();
+            let map: HashMap<String, AttributeValue> = (&task).into();
             assert_eq!(
                 map["sk"],
                 AttributeValue::S("$Task#employee_e42069".to_string())
             );
         }
     }
+
+    #[test]
+    fn parse_keys() {
+        let task = Task {
+            project: Some("a#b".to_string()),
+            employee: Some("c\\d".to_string()),
+            ..Default::default()
+        };
+        let keys = task.primary_keys();
+        let range = keys.range.as_ref().unwrap();
+        assert_eq!(keys.hash.composite, "$TaskService#Task#task_id_123");
+        assert_eq!(range.composite, "$Task#employee_c\\\\d#project_a\\#b");
+
+        let parts = Task::parse_primary_key(&keys.hash.composite, &range.composite).unwrap();
+        assert_eq!(parts.task_id, task.task_id);
+        assert_eq!(parts.project, task.project);
+        assert_eq!(parts.employee, task.employee);
+
+        // partial keys leave the missing fields `None`
+        let parts = Task::parse_gsi1_key("$TaskService#Task#project_a\\#b", "$Task#employee_e").unwrap();
+        assert_eq!(parts.project.as_deref(), Some("a#b"));
+        assert_eq!(parts.employee.as_deref(), Some("e"));
+        assert_eq!(parts.task_id, None);
+
+        match Task::parse_primary_key("$TaskService#Task#task_id_1", "$Task#project_p").err() {
+            Some(DeezError::InvalidKey { key, .. }) => assert_eq!(key, "$Task#project_p"),
+            x => panic!("unexpected result: {:?}", x),
+        }
+        assert!(Task::parse_primary_key("$TaskService#Task#task_id_1#x", "$Task").is_err());
+
+        // values are read as the type of their field
+        let m = Membership {
+            org_id: "acme".to_string(),
+            status: Status::InProgress,
+            seat: 7,
+            user_id: Some(UserId(3)),
+        };
+        let keys = m.primary_keys();
+        let parts = Membership::parse_primary_key(&keys.hash.composite, &keys.range.unwrap().composite).unwrap();
+        assert_eq!(parts.org_id.as_deref(), Some("acme"));
+        assert_eq!(parts.status, Some(Status::InProgress));
+        assert_eq!(parts.seat, Some(7));
+        assert_eq!(parts.user_id, Some(UserId(3)));
+        match Membership::parse_primary_key("$TaskService#Membership#org_id_acme", "$Membership#status_open#seat_x").err() {
+            Some(DeezError::InvalidNumber { entity, field, .. }) => {
+                assert_eq!(entity, "Membership");
+                assert_eq!(field, "seat");
+            }
+            x => panic!("unexpected result: {:?}", x),
+        }
+
+        // templates and key formats
+        let parts = OrgUser::parse_primary_key("ORG#a\\#b", "USER#user-7#PROFILE").unwrap();
+        assert_eq!(parts.org_id.as_deref(), Some("a#b"));
+        assert_eq!(parts.user_id, Some(UserId(7)));
+        let parts = OrgUser::parse_primary_key("ORG#acme", "USER#").unwrap();
+        assert_eq!(parts.user_id, None);
+        let parts = Contact::parse_primary_key("$contactservice|contact|email:ddp@deez.nuts", "$contact|list:a\\|b").unwrap();
+        assert_eq!(parts.email.as_deref(), Some("ddp@deez.nuts"));
+        assert_eq!(parts.list.as_deref(), Some("a|b"));
+        assert_eq!(parts.name, None);
+
+        // hash-only indexes take only the hash key
+        let parts = Account::parse_gsi1_key("$AccountService#Account#email_ddp@deez.nuts").unwrap();
+        assert_eq!(parts.email.as_deref(), Some("ddp@deez.nuts"));
+    }
+
+    #[test]
+    fn key_encodings() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let reading = |sequence: u32, delta: i32, value: f64| Reading {
+            sensor: "s1".to_string(),
+            sequence,
+            delta,
+            value,
+            taken_at: Some(UNIX_EPOCH + Duration::from_millis(1_700_000_000_123)),
+        };
+
+        let a = reading(42, -3, -0.5);
+        let keys = a.primary_keys();
+        let range = keys.range.unwrap().composite;
+        assert_eq!(range, "$Reading#sequence_000042#taken_at_1700000000123");
+        // `taken_at` is stored according to its type, only the key is in epoch millis
+        assert_ne!(a.to_item()["taken_at"], AttributeValue::N("1700000000123".to_string()));
+        let parts = Reading::parse_primary_key(&keys.hash.composite, &range).unwrap();
+        assert_eq!(parts.sequence, Some(42));
+        assert_eq!(parts.taken_at, a.taken_at);
+
+        // timestamps before the epoch sort first
+        let b = Reading {
+            taken_at: Some(UNIX_EPOCH - Duration::from_millis(1)),
+            ..reading(42, -3, -0.5)
+        };
+        let range = b.primary_keys().range.unwrap().composite;
+        assert_eq!(range, "$Reading#sequence_000042#taken_at_-9999999999999");
+        assert!(range < a.primary_keys().range.unwrap().composite);
+        let parts = Reading::parse_primary_key(&keys.hash.composite, &range).unwrap();
+        assert_eq!(parts.taken_at, b.taken_at);
+
+        // keys sort in numeric order
+        let sorted = [
+            reading(9, -10, 1.0),
+            reading(10, -9, 1.0),
+            reading(11, -1, -2.5),
+            reading(12, -1, 0.25),
+            reading(100, 0, 0.0),
+            reading(1000, 10, -1.0),
+        ];
+        let sk = |r: &Reading, index: &str| match index {
+            "primary" => r.primary_keys().range.unwrap().composite,
+            _ => r.by_delta_keys().range.unwrap().composite,
+        };
+        for index in ["primary", "by_delta"] {
+            assert!(sorted.windows(2).all(|w| sk(&w[0], index) < sk(&w[1], index)));
+        }
+
+        let keys = a.by_delta_keys();
+        let parts = Reading::parse_by_delta_key(&keys.hash.composite, &keys.range.unwrap().composite).unwrap();
+        assert_eq!(parts.sensor.as_deref(), Some("s1"));
+        assert_eq!(parts.delta, Some(-3));
+        assert_eq!(parts.value, Some(-0.5));
+        assert_eq!(parts.sequence, None);
+    }
+
+    #[test]
+    fn key_types() {
+        use aws_sdk_dynamodb::primitives::Blob;
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let mut a = Score {
+            game: "chess".to_string(),
+            player: "ddp".to_string(),
+            points: Some(-42),
+            fingerprint: Some(Blob::new(vec![0xde, 0xe2])),
+            scored_at: Some(UNIX_EPOCH - Duration::from_secs(42)),
+        };
+
+        let keys = a.by_points_keys();
+        let range = keys.range.as_ref().unwrap();
+        assert_eq!(keys.hash.key_type, KeyType::String);
+        assert_eq!(range.key_type, KeyType::Number);
+        assert_eq!(range.av(), AttributeValue::N("-42".to_string()));
+        assert_eq!(a.by_fingerprint_keys().hash.av(), AttributeValue::B(Blob::new(vec![0xde, 0xe2])));
+
+        let m = a.to_item();
+        assert_eq!(m["gsi1sk"], AttributeValue::N("-42".to_string()));
+        assert_eq!(m["gsi2pk"], AttributeValue::B(Blob::new(vec![0xde, 0xe2])));
+        assert_eq!(m["sk"], AttributeValue::S("$Score#player_ddp".to_string()));
+
+        let parts = Score::parse_by_points_key(&keys.hash.composite, &range.composite).unwrap();
+        assert_eq!(parts.game.as_deref(), Some("chess"));
+        assert_eq!(parts.points, Some(-42));
+        let parts = Score::parse_by_fingerprint_key("dee2").unwrap();
+        assert_eq!(parts.fingerprint, a.fingerprint);
+
+        let condition = keys.key_condition(Some(RangeCondition::Gt)).unwrap();
+        assert_eq!(condition.expression, "#pk = :pk AND #sk > :sk");
+        assert_eq!(condition.names["#sk"], "gsi1sk");
+        assert_eq!(condition.values[":sk"], AttributeValue::N("-42".to_string()));
+        let upper = Score {
+            game: "chess".to_string(),
+            player: "ddp".to_string(),
+            points: Some(7),
+            fingerprint: None,
+            scored_at: None,
+        };
+        let upper = upper.by_points_keys().range.unwrap();
+        let condition = keys.key_condition(Some(RangeCondition::Between(upper))).unwrap();
+        assert_eq!(condition.expression, "#pk = :pk AND #sk BETWEEN :sk AND :sk2");
+        assert_eq!(condition.values[":sk2"], AttributeValue::N("7".to_string()));
+        assert!(keys.key_condition(Some(RangeCondition::BeginsWith)).is_err());
+        assert_eq!(a.by_fingerprint_keys().key_condition(None).unwrap().expression, "#pk = :pk");
+        assert!(a.by_fingerprint_keys().key_condition(Some(RangeCondition::Eq)).is_err());
+        let mut hash = a.by_fingerprint_keys().hash;
+        hash.composite = "dee".to_string();
+        match hash.try_av().err() {
+            Some(DeezError::InvalidKey { key, .. }) => assert_eq!(key, "dee"),
+            x => panic!("unexpected result: {:?}", x),
+        }
+
+        // epoch timestamps are plain numbers in number keys
+        let keys = a.by_time_keys();
+        assert_eq!(keys.hash.av(), AttributeValue::N("-42".to_string()));
+        let parts = Score::parse_by_time_key(&keys.hash.composite).unwrap();
+        assert_eq!(parts.scored_at, a.scored_at);
+
+        // number and binary keys can not be empty, so they are left out
+        a.points = None;
+        a.fingerprint = None;
+        assert!(!a.by_points_keys().is_complete());
+        let m = a.to_item();
+        assert!(m.contains_key("gsi1pk"));
+        assert!(!m.contains_key("gsi1sk"));
+        assert!(!m.contains_key("gsi2pk"));
+    }
+
+    #[test]
+    fn conversion_errors() {
+        let item: HashMap<String, AttributeValue> = Task::default().into();
+
+        {
+            let mut m = item.clone();
+            m.remove("description");
+            match Task::try_from(&m) {
+                Err(DeezError::MissingAttribute { entity, field }) => {
+                    assert_eq!(entity, "Task");
+                    assert_eq!(field, "description");
+                }
+                x => panic!("unexpected result: {:?}", x),
+            }
+        }
+
+        {
+            let mut m = item.clone();
+            m.insert("description".to_string(), AttributeValue::N("1".to_string()));
+            match Task::try_from(&m) {
+                Err(DeezError::UnexpectedVariant { field, expected, .. }) => {
+                    assert_eq!(field, "description");
+                    assert_eq!(expected, "S");
+                }
+                x => panic!("unexpected result: {:?}", x),
+            }
+        }
+
+        {
+            let mut m: HashMap<String, AttributeValue> = Buss::default().into();
+            m.insert("num_set".to_string(), AttributeValue::Ns(vec!["nan?".to_string()]));
+            match Buss::try_from(&m) {
+                Err(DeezError::InvalidNumber { entity, field, value }) => {
+                    assert_eq!(entity, "Buss");
+                    assert_eq!(field, "num_set");
+                    assert_eq!(value, "nan?");
+                }
+                x => panic!("unexpected result: {:?}", x),
+            }
+        }
+
+        {
+            let mut bad = item.clone();
+            bad.remove("description");
+            match TaskItems::try_from(&[item.clone(), item.clone(), bad][..]) {
+                Err(DeezError::Item { index, source }) => {
+                    assert_eq!(index, 2);
+                    assert!(matches!(*source, DeezError::MissingAttribute { .. }));
+                }
+                x => panic!("unexpected result: {:?}", x),
+            }
+        }
+    }
+
+    #[test]
+    fn integers() {
+        let m: HashMap<String, AttributeValue> = Buss::default().into();
+        assert_eq!(m["int"], AttributeValue::N("-9007199254740993".to_string()));
+        assert_eq!(
+            m["int_set"],
+            AttributeValue::Ns(vec!["34028236692093846346337460743176821145".to_string()])
+        );
+
+        let b = Buss::try_from(&m).unwrap();
+        assert_eq!(b.int, -9007199254740993);
+        assert_eq!(b.int_opt, Some(u32::MAX));
+        assert_eq!(b.int_list, vec![0, 255]);
+        assert_eq!(b.int_opt_list, Some(vec![-1]));
+        assert_eq!(b.int_set, vec![u128::MAX / 10]);
+        assert_eq!(b.int_opt_set, Some(vec![isize::MIN]));
+        assert!(b.try_to_item().is_ok());
+
+        // DynamoDB numbers have at most 38 digits
+        let b = Buss {
+            int_set: vec![u128::MAX],
+            ..Default::default()
+        };
+        match b.try_to_item() {
+            Err(DeezError::InvalidValue { entity, field, value, .. }) => {
+                assert_eq!(entity, "Buss");
+                assert_eq!(field, "int_set");
+                assert_eq!(value, u128::MAX.to_string());
+            }
+            x => panic!("unexpected result: {:?}", x),
+        }
+
+        let mut m = m;
+        m.insert(
+            "int_list".to_string(),
+            AttributeValue::L(vec![AttributeValue::N("256".to_string())]),
+        );
+        match Buss::try_from(&m) {
+            Err(DeezError::NumberOverflow { field, value, ty, .. }) => {
+                assert_eq!(field, "int_list");
+                assert_eq!(value, "256");
+                assert_eq!(ty, "u8");
+            }
+            x => panic!("unexpected result: {:?}", x),
+        }
+
+        m.insert("int".to_string(), AttributeValue::N("1.5".to_string()));
+        assert!(matches!(
+            Buss::try_from(&m),
+            Err(DeezError::InvalidNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn numbers() {
+        let m: HashMap<String, AttributeValue> = Buss::default().into();
+        assert_eq!(
+            m["number"],
+            AttributeValue::N("12345678901234567890123456789012345678".to_string())
+        );
+        assert_eq!(m["number_set"], AttributeValue::Ns(vec!["19.99".to_string()]));
+
+        let b = Buss::try_from(&m).unwrap();
+        assert_eq!(b.number.as_str(), "12345678901234567890123456789012345678");
+        assert_eq!(b.number_opt, Some("0.1".parse().unwrap()));
+        assert_eq!(b.number_list[1].as_str(), "-1.5E-7");
+
+        let mut m = m;
+        m.insert("number".to_string(), AttributeValue::N("1E+200".to_string()));
+        assert!(matches!(
+            Buss::try_from(&m),
+            Err(DeezError::InvalidNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn maps() {
+        let m: HashMap<String, AttributeValue> = Buss::default().into();
+        assert_eq!(
+            m["string_map"],
+            AttributeValue::M(HashMap::from([(
+                "en".to_string(),
+                AttributeValue::S("a".to_string())
+            )]))
+        );
+        match &m["bar_map"] {
+            AttributeValue::M(bars) => assert!(matches!(bars["a"], AttributeValue::M(_))),
+            x => panic!("unexpected attribute: {:?}", x),
+        }
+
+        let b = Buss::try_from(&m).unwrap();
+        assert_eq!(b.string_map, Buss::default().string_map);
+        assert_eq!(b.int_map, Buss::default().int_map);
+        assert_eq!(b.number_opt_map, Buss::default().number_opt_map);
+        assert_eq!(b.bar_map["a"].bar_string_2, "barbar");
+
+        let mut m = m;
+        m.insert("number_opt_map".to_string(), AttributeValue::Null(true));
+        m.insert(
+            "int_map".to_string(),
+            AttributeValue::M(HashMap::from([(
+                "a".to_string(),
+                AttributeValue::S("1".to_string()),
+            )])),
+        );
+        match Buss::try_from(&m) {
+            Err(DeezError::UnexpectedVariant { field, expected, .. }) => {
+                assert_eq!(field, "int_map");
+                assert_eq!(expected, "N");
+            }
+            x => panic!("unexpected result: {:?}", x),
+        }
+
+        m.insert("int_map".to_string(), AttributeValue::M(HashMap::new()));
+        let b = Buss::try_from(&m).unwrap();
+        assert!(b.int_map.is_empty());
+        assert_eq!(b.number_opt_map, None);
+    }
+
+    #[test]
+    fn sets() {
+        let m: HashMap<String, AttributeValue> = Buss::default().into();
+        assert_eq!(
+            m["int_btree_set"],
+            AttributeValue::Ns(vec!["1".to_string(), "2".to_string(), "3".to_string()])
+        );
+        match &m["string_hash_set"] {
+            AttributeValue::Ss(ss) => assert_eq!(ss.len(), 2),
+            x => panic!("unexpected attribute: {:?}", x),
+        }
+
+        let b = Buss::try_from(&m).unwrap();
+        assert_eq!(b.string_hash_set, Buss::default().string_hash_set);
+        assert_eq!(b.int_btree_set, Buss::default().int_btree_set);
+        assert_eq!(b.number_opt_hash_set, Buss::default().number_opt_hash_set);
+        assert_eq!(b.blob_hash_set, Buss::default().blob_hash_set);
+
+        let mut m = m;
+        m.insert(
+            "int_btree_set".to_string(),
+            AttributeValue::Ns(vec!["2".to_string(), "1".to_string(), "2".to_string()]),
+        );
+        let b = Buss::try_from(&m).unwrap();
+        assert_eq!(b.int_btree_set.into_iter().collect::<Vec<_>>(), vec![1, 2]);
+
+        m.insert(
+            "string_hash_set".to_string(),
+            AttributeValue::L(vec![AttributeValue::S("a".to_string())]),
+        );
+        match Buss::try_from(&m) {
+            Err(DeezError::UnexpectedVariant { field, expected, .. }) => {
+                assert_eq!(field, "string_hash_set");
+                assert_eq!(expected, "Ss");
+            }
+            x => panic!("unexpected result: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn renames() {
+        let a = Legacy {
+            legacy_id: Some("l1".to_string()),
+            created_at: Some("2015".to_string()),
+            display_name: "deez".to_string(),
+            r#type: "old".to_string(),
+            owner: LegacyOwner {
+                user_name: "ddp".to_string(),
+            },
+            version: 2,
+            region: "eu-west-1".to_string(),
+        };
+
+        let m: HashMap<String, AttributeValue> = a.into();
+        assert_eq!(
+            m["pk"],
+            AttributeValue::S("$LegacyService#Legacy#legacyId_l1".to_string())
+        );
+        assert_eq!(m["sk"], AttributeValue::S("$Legacy#created_2015".to_string()));
+        assert_eq!(m["legacyId"], AttributeValue::S("l1".to_string()));
+        assert_eq!(m["created"], AttributeValue::S("2015".to_string()));
+        assert_eq!(m["displayName"], AttributeValue::S("deez".to_string()));
+        assert_eq!(m["type"], AttributeValue::S("old".to_string()));
+        assert_eq!(
+            m["owner"],
+            AttributeValue::M(HashMap::from([(
+                "USER_NAME".to_string(),
+                AttributeValue::S("ddp".to_string())
+            )]))
+        );
+        assert!(!m.contains_key("display_name"));
+
+        let b = Legacy::try_from(&m).unwrap();
+        assert_eq!(b.created_at, Some("2015".to_string()));
+        assert_eq!(b.owner.user_name, "ddp");
+
+        // items written before the rename are read through the alias
+        let mut m = m;
+        let display_name = m.remove("displayName").unwrap();
+        m.insert("display_name".to_string(), display_name);
+        let b = Legacy::try_from(&m).unwrap();
+        assert_eq!(b.display_name, "deez");
+
+        m.remove("display_name");
+        match Legacy::try_from(&m) {
+            Err(DeezError::MissingAttribute { field, .. }) => assert_eq!(field, "displayName"),
+            x => panic!("unexpected result: {:?}", x),
+        }
+    }
+
+    #[test]
+    fn custom_types() {
+        let a = Profile {
+            user_id: Some(UserId(7)),
+            name: "ddp".to_string(),
+            tags: vec!["a".to_string()],
+            friends: vec![UserId(8)],
+        };
+
+        let m: HashMap<String, AttributeValue> = a.into();
+        assert_eq!(
+            m["pk"],
+            AttributeValue::S("$ProfileService#Profile#user_id_user-7".to_string())
+        );
+        assert_eq!(m["user_id"], AttributeValue::S("user-7".to_string()));
+        assert_eq!(
+            m["friends"],
+            AttributeValue::L(vec![AttributeValue::S("user-8".to_string())])
+        );
+
+        let b = Profile::try_from(&m).unwrap();
+        assert_eq!(b.user_id, Some(UserId(7)));
+        assert_eq!(b.name, "ddp");
+        assert_eq!(b.tags, vec!["a".to_string()]);
+        assert_eq!(b.friends, vec![UserId(8)]);
+
+        let mut m = m;
+        m.insert(
+            "friends".to_string(),
+            AttributeValue::L(vec![AttributeValue::S("bot-9".to_string())]),
+        );
+        match Profile::try_from(&m) {
+            Err(DeezError::InvalidValue { entity, field, value, .. }) => {
+                assert_eq!(entity, "Profile");
+                assert_eq!(field, "friends");
+                assert_eq!(value, "bot-9");
+            }
+            x => panic!("unexpected result: {:?}", x),
+        }
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn decimals() {
+        use rust_decimal::Decimal;
+        use std::str::FromStr;
+
+        let p = Price {
+            amount: Decimal::from_str("0.30").unwrap(),
+            amount_opt: None,
+            amount_set: vec![Decimal::from_str("1.1").unwrap(), Decimal::from(2)],
+        };
+        let m: HashMap<String, AttributeValue> = p.into();
+        assert_eq!(m["amount"], AttributeValue::N("0.30".to_string()));
+
+        let p = Price::try_from(&m).unwrap();
+        assert_eq!(p.amount, Decimal::from_str("0.3").unwrap());
+        assert_eq!(p.amount_opt, None);
+        assert_eq!(p.amount_set.len(), 2);
+    }
+
+    #[test]
+    fn timestamps() {
+        use std::time::{Duration, UNIX_EPOCH};
+
+        let r = Reminder {
+            reminder_id: Some("r1".to_string()),
+            remind_at: Some(UNIX_EPOCH + Duration::from_millis(1420167845006)),
+            created_at: UNIX_EPOCH + Duration::from_millis(1420167845006),
+            expires_at: Some(UNIX_EPOCH + Duration::from_millis(1420167845006)),
+        };
+
+        let m = r.to_item();
+        assert_eq!(
+            m["sk"],
+            AttributeValue::S("$Reminder#remind_at_1420167845006".to_string())
+        );
+        assert_eq!(
+            m["created_at"],
+            AttributeValue::S("2015-01-02T03:04:05.006Z".to_string())
+        );
+        assert_eq!(m["remind_at"], AttributeValue::N("1420167845006".to_string()));
+        assert_eq!(m["expires_at"], AttributeValue::N("1420167845".to_string()));
+
+        let c = Reminder::try_from(&m).unwrap();
+        assert_eq!(c.remind_at, r.remind_at);
+        assert_eq!(c.created_at, r.created_at);
+        assert_eq!(
+            c.expires_at,
+            Some(UNIX_EPOCH + Duration::from_millis(1420167845000))
+        );
+
+        // zero-padded so earlier times sort first
+        let early: HashMap<String, AttributeValue> = Reminder {
+            reminder_id: Some("r1".to_string()),
+            remind_at: Some(UNIX_EPOCH + Duration::from_millis(999)),
+            ..Default::default()
+        }
+        .into();
+        assert_eq!(
+            early["sk"],
+            AttributeValue::S("$Reminder#remind_at_0000000000999".to_string())
+        );
+
+        let mut m = m;
+        m.insert("created_at".to_string(), AttributeValue::S("yesterday".to_string()));
+        assert!(matches!(
+            Reminder::try_from(&m),
+            Err(DeezError::InvalidTimestamp { .. })
+        ));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn chrono_timestamps() {
+        use chrono::{NaiveDate, TimeZone, Utc};
+
+        let e = Event {
+            calendar: Some("work".to_string()),
+            starts_at: Some(Utc.with_ymd_and_hms(2015, 1, 2, 3, 4, 5).unwrap()),
+            due_on: NaiveDate::from_ymd_opt(2015, 1, 9),
+            reminders: vec![Utc.with_ymd_and_hms(2015, 1, 1, 0, 0, 0).unwrap()],
+        };
+
+        let m: HashMap<String, AttributeValue> = e.clone().into();
+        assert_eq!(
+            m["sk"],
+            AttributeValue::S("$Event#starts_at_2015-01-02T03:04:05.000000000Z".to_string())
+        );
+        assert_eq!(m["due_on"], AttributeValue::S("2015-01-09".to_string()));
+        assert_eq!(m["reminders"], AttributeValue::Ns(vec!["1420070400000".to_string()]));
+
+        let c = Event::try_from(&m).unwrap();
+        assert_eq!(c.starts_at, e.starts_at);
+        assert_eq!(c.due_on, e.due_on);
+        assert_eq!(c.reminders, e.reminders);
+    }
+
+    #[test]
+    fn enums() {
+        let t = Ticket {
+            shape: Shape::Circle {
+                radius: 1.5,
+                label: None,
+            },
+            activity: Some(Activity::Comment {
+                text: "lgtm".to_string(),
+                edited: false,
+            }),
+            ..Default::default()
+        };
+
+        let m: HashMap<String, AttributeValue> = t.into();
+        assert_eq!(
+            m["gsi1sk"],
+            AttributeValue::S("$Ticket#status_in_progress".to_string())
+        );
+        assert_eq!(m["status"], AttributeValue::S("in_progress".to_string()));
+        // keys use the stored name rather than the Display impl
+        assert_eq!(IntoAttributeValue::to_key_part(&Status::InProgress), "in_progress");
+        assert_eq!(IntoAttributeValue::to_av(&Status::Done), AttributeValue::S("Done".to_string()));
+        assert_eq!(Status::from_key_part("in_progress").unwrap(), Status::InProgress);
+        assert_eq!(
+            m["history"],
+            AttributeValue::L(vec![
+                AttributeValue::S("open".to_string()),
+                AttributeValue::S("in_progress".to_string()),
+            ])
+        );
+        assert_eq!(
+            m["shape"],
+            AttributeValue::M(HashMap::from([(
+                "Circle".to_string(),
+                AttributeValue::M(HashMap::from([
+                    ("radius".to_string(), AttributeValue::N("1.5".to_string())),
+                    ("label".to_string(), AttributeValue::Null(true)),
+                ])),
+            )]))
+        );
+        assert_eq!(
+            m["activity"],
+            AttributeValue::M(HashMap::from([
+                ("kind".to_string(), AttributeValue::S("Comment".to_string())),
+                ("text".to_string(), AttributeValue::S("lgtm".to_string())),
+                ("edited".to_string(), AttributeValue::Bool(false)),
+            ]))
+        );
+
+        let t = Ticket::try_from(&m).unwrap();
+        assert_eq!(t.status, Some(Status::InProgress));
+        assert_eq!(t.history, vec![Status::Open, Status::InProgress]);
+        assert_eq!(
+            t.shape,
+            Shape::Circle {
+                radius: 1.5,
+                label: None
+            }
+        );
+        assert!(matches!(
+            t.activity,
+            Some(Activity::Comment { ref text, edited: false }) if text == "lgtm"
+        ));
+
+        for (shape, av) in [
+            (Shape::Point, AttributeValue::S("Point".to_string())),
+            (
+                Shape::Label("x".to_string()),
+                AttributeValue::M(HashMap::from([(
+                    "Label".to_string(),
+                    AttributeValue::S("x".to_string()),
+                )])),
+            ),
+        ] {
+            assert_eq!(AttributeValue::from(shape.clone()), av);
+            assert_eq!(Shape::try_from(&av).unwrap(), shape);
+        }
+
+        let a: AttributeValue = Activity::Attachment(Baz::default()).into();
+        assert_eq!(a.as_m().unwrap()["kind"], AttributeValue::S("Attachment".to_string()));
+        assert_eq!(a.as_m().unwrap()["baz_string_1"], AttributeValue::S("baz".to_string()));
+        assert!(matches!(
+            Activity::try_from(&a).unwrap(),
+            Activity::Attachment(Baz { ref baz_string_2, .. }) if baz_string_2 == "bazbaz"
+        ));
+        let a: AttributeValue = Activity::Idle.into();
+        assert!(matches!(Activity::try_from(&a).unwrap(), Activity::Idle));
+
+        let mut m = m;
+        m.insert("status".to_string(), AttributeValue::S("closed".to_string()));
+        match Ticket::try_from(&m) {
+            Err(DeezError::UnknownVariant { entity, field, value }) => {
+                assert_eq!(entity, "Ticket");
+                assert_eq!(field, "status");
+                assert_eq!(value, "closed");
+            }
+            x => panic!("unexpected result: {:?}", x),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "`Shape::Label` carries data and can not be used in a composite key")]
+    fn data_variant_key_part() {
+        IntoAttributeValue::to_key_part(&Shape::Label("x".to_string()));
+    }
 }
@@ -0,0 +1,97 @@
+use thiserror::Error;
+
+/// Errors returned when converting DynamoDB items back into entities.
+#[derive(Debug, Error)]
+pub enum DeezError {
+    #[error("missing attribute `{field}` for `{entity}`")]
+    MissingAttribute { entity: String, field: String },
+
+    #[error("unexpected AttributeValue variant for `{field}` of `{entity}`, expected `{expected}`")]
+    UnexpectedVariant {
+        entity: String,
+        field: String,
+        expected: &'static str,
+    },
+
+    #[error("unknown enum variant `{value}` for `{field}` of `{entity}`")]
+    UnknownVariant {
+        entity: String,
+        field: String,
+        value: String,
+    },
+
+    #[error("could not parse number `{value}` for `{field}` of `{entity}`")]
+    InvalidNumber {
+        entity: String,
+        field: String,
+        value: String,
+    },
+
+    #[error("number `{value}` for `{field}` of `{entity}` does not fit in `{ty}`")]
+    NumberOverflow {
+        entity: String,
+        field: String,
+        value: String,
+        ty: &'static str,
+    },
+
+    #[error("could not parse timestamp `{value}` for `{field}` of `{entity}`")]
+    InvalidTimestamp {
+        entity: String,
+        field: String,
+        value: String,
+    },
+
+    #[error("invalid value `{value}` for `{field}` of `{entity}`: {reason}")]
+    InvalidValue {
+        entity: String,
+        field: String,
+        value: String,
+        reason: String,
+    },
+
+    #[error("`{value}` is not a valid DynamoDB number: {reason}")]
+    NumberValidation { value: String, reason: &'static str },
+
+    #[error("could not parse key `{key}`: {reason}")]
+    InvalidKey { key: String, reason: String },
+
+    #[error("could not convert item at index {index}: {source}")]
+    Item {
+        index: usize,
+        source: Box<DeezError>,
+    },
+}
+
+impl DeezError {
+    /// Adds the entity and field to errors raised while converting a nested
+    /// `AttributeValue`, which is done without knowing where it is stored.
+    #[doc(hidden)]
+    pub fn in_field(mut self, entity: &str, field: &str) -> DeezError {
+        match &mut self {
+            DeezError::UnexpectedVariant {
+                entity: e, field: f, ..
+            }
+            | DeezError::UnknownVariant {
+                entity: e, field: f, ..
+            }
+            | DeezError::InvalidNumber {
+                entity: e, field: f, ..
+            }
+            | DeezError::NumberOverflow {
+                entity: e, field: f, ..
+            }
+            | DeezError::InvalidTimestamp {
+                entity: e, field: f, ..
+            }
+            | DeezError::InvalidValue {
+                entity: e, field: f, ..
+            } if f.is_empty() => {
+                *e = entity.to_string();
+                *f = field.to_string();
+            }
+            _ => {}
+        }
+        self
+    }
+}
@@ -0,0 +1,292 @@
+use crate::DeezError;
+use std::borrow::Cow;
+
+const ESCAPE: char = '\\';
+
+/// Escapes the characters of `delimiter` and the escape character `\` in a key
+/// part, so that composed keys can be split back into their parts.
+#[doc(hidden)]
+pub fn escape_key_part<'a>(value: &'a str, delimiter: &str) -> Cow<'a, str> {
+    let escaped = |c: char| c == ESCAPE || delimiter.contains(c);
+    if !value.contains(escaped) {
+        return Cow::Borrowed(value);
+    }
+    let mut s = String::with_capacity(value.len() + delimiter.len());
+    for c in value.chars() {
+        if escaped(c) {
+            s.push(ESCAPE);
+        }
+        s.push(c);
+    }
+    Cow::Owned(s)
+}
+
+/// Removes the zeros added by the `pad` option of a composite, keeping a zero
+/// before a non-digit or at the end, e.g. `000` and `00.5` become `0` and `0.5`.
+#[doc(hidden)]
+pub fn unpad_key_part(s: &str) -> &str {
+    let trimmed = s.trim_start_matches('0');
+    match trimmed.chars().next() {
+        Some(c) if c.is_ascii_digit() => trimmed,
+        _ if trimmed.len() < s.len() => &s[s.len() - trimmed.len() - 1..],
+        _ => trimmed,
+    }
+}
+
+/// Formats an epoch timestamp zero-padded to `width` digits, used for the
+/// `epoch_seconds` and `epoch_millis` formats in composite keys. Negative values
+/// are written as `-` and their distance from `-10^width`, so that they sort
+/// before positive ones and in order, down to `-10^width + 1`.
+#[doc(hidden)]
+pub fn epoch_key_part(value: i64, width: usize) -> String {
+    if value < 0 {
+        format!("-{:01$}", 10i64.pow(width as u32) + value, width)
+    } else {
+        format!("{:01$}", value, width)
+    }
+}
+
+/// The inverse of `epoch_key_part`.
+#[doc(hidden)]
+pub fn parse_epoch_key_part(s: &str, width: usize) -> Option<i64> {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let value: i64 = digits.parse().ok()?;
+    match s.starts_with('-') {
+        true => Some(value - 10i64.pow(width as u32)),
+        false => Some(value),
+    }
+}
+
+/// Numbers encoded so that composite keys sort in numeric order, used by the
+/// `sortable` option of a composite, e.g. `#[deez_primary(key = "range", sortable)]`.
+///
+/// Integers are zero-padded to the width of their largest value, with the
+/// sign bit flipped for signed integers so that negative numbers come first.
+/// Floats are the hex encoding of their bits, flipped to sort in the same way.
+pub trait SortableKeyPart: Sized {
+    fn to_sortable_key_part(&self) -> String;
+
+    fn from_sortable_key_part(s: &str) -> Result<Self, DeezError>;
+}
+
+fn invalid_number(s: &str) -> DeezError {
+    DeezError::InvalidNumber {
+        entity: String::new(),
+        field: String::new(),
+        value: s.to_string(),
+    }
+}
+
+macro_rules! sortable_unsigned {
+    ($($t:ty, $width:expr);*) => {
+        $(
+            impl SortableKeyPart for $t {
+                fn to_sortable_key_part(&self) -> String {
+                    format!("{:01$}", self, $width)
+                }
+
+                fn from_sortable_key_part(s: &str) -> Result<$t, DeezError> {
+                    s.parse().map_err(|_| invalid_number(s))
+                }
+            }
+        )*
+    };
+}
+
+sortable_unsigned!(u8, 3; u16, 5; u32, 10; u64, 20; u128, 39; usize, 20);
+
+macro_rules! sortable_signed {
+    ($($t:ty, $u:ty, $width:expr);*) => {
+        $(
+            impl SortableKeyPart for $t {
+                fn to_sortable_key_part(&self) -> String {
+                    format!("{:01$}", (*self as $u) ^ (1 << (<$u>::BITS - 1)), $width)
+                }
+
+                fn from_sortable_key_part(s: &str) -> Result<$t, DeezError> {
+                    let u: $u = s.parse().map_err(|_| invalid_number(s))?;
+                    Ok((u ^ (1 << (<$u>::BITS - 1))) as $t)
+                }
+            }
+        )*
+    };
+}
+
+sortable_signed!(i8, u8, 3; i16, u16, 5; i32, u32, 10; i64, u64, 20; i128, u128, 39; isize, usize, 20);
+
+macro_rules! sortable_float {
+    ($($t:ty, $u:ty);*) => {
+        $(
+            impl SortableKeyPart for $t {
+                fn to_sortable_key_part(&self) -> String {
+                    let sign: $u = 1 << (<$u>::BITS - 1);
+                    let bits = self.to_bits();
+                    let bits = if bits & sign != 0 { !bits } else { bits | sign };
+                    format!("{:01$x}", bits, (<$u>::BITS / 4) as usize)
+                }
+
+                fn from_sortable_key_part(s: &str) -> Result<$t, DeezError> {
+                    let sign: $u = 1 << (<$u>::BITS - 1);
+                    let bits = <$u>::from_str_radix(s, 16).map_err(|_| invalid_number(s))?;
+                    let bits = if bits & sign != 0 { bits ^ sign } else { !bits };
+                    Ok(<$t>::from_bits(bits))
+                }
+            }
+        )*
+    };
+}
+
+sortable_float!(f32, u32; f64, u64);
+
+/// Reads a composed key from the start, used by the generated `parse_*_key`
+/// functions.
+#[doc(hidden)]
+pub struct KeyReader<'a> {
+    key: &'a str,
+    rest: &'a str,
+}
+
+impl<'a> KeyReader<'a> {
+    pub fn new(key: &'a str) -> KeyReader<'a> {
+        KeyReader { key, rest: key }
+    }
+
+    /// Whether the whole key has been read, which ends a partial key.
+    pub fn is_empty(&self) -> bool {
+        self.rest.is_empty()
+    }
+
+    /// Reads `literal`, which must come next.
+    pub fn literal(&mut self, literal: &str) -> Result<(), DeezError> {
+        match self.rest.strip_prefix(literal) {
+            Some(rest) => {
+                self.rest = rest;
+                Ok(())
+            }
+            None => Err(self.invalid(format!("expected `{}` at `{}`", literal, self.rest))),
+        }
+    }
+
+    /// Reads and unescapes a value up to the next unescaped `end`, or to the
+    /// end of the key if `end` is empty or not found.
+    pub fn value(&mut self, end: &str) -> String {
+        let mut value = String::new();
+        let mut i = 0;
+        while i < self.rest.len() {
+            let rest = &self.rest[i..];
+            if !end.is_empty() && rest.starts_with(end) {
+                break;
+            }
+            let mut chars = rest.chars();
+            let c = chars.next().unwrap();
+            i += c.len_utf8();
+            match (c, chars.next()) {
+                (ESCAPE, Some(escaped)) => {
+                    value.push(escaped);
+                    i += escaped.len_utf8();
+                }
+                _ => value.push(c),
+            }
+        }
+        self.rest = &self.rest[i..];
+        value
+    }
+
+    /// Checks that nothing is left after the last part.
+    pub fn finish(self) -> Result<(), DeezError> {
+        match self.rest.is_empty() {
+            true => Ok(()),
+            false => Err(self.invalid(format!("unexpected `{}`", self.rest))),
+        }
+    }
+
+    fn invalid(&self, reason: String) -> DeezError {
+        DeezError::InvalidKey {
+            key: self.key.to_string(),
+            reason,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape() {
+        assert_eq!(escape_key_part("abc", "#"), "abc");
+        assert!(matches!(escape_key_part("abc", "#"), Cow::Borrowed(_)));
+        assert_eq!(escape_key_part("a#b", "#"), "a\\#b");
+        assert_eq!(escape_key_part("a\\b", "#"), "a\\\\b");
+        assert_eq!(escape_key_part("a||b|", "||"), "a\\|\\|b\\|");
+    }
+
+    #[test]
+    fn sortable() {
+        fn check<T: SortableKeyPart + PartialEq + std::fmt::Debug>(values: &[T]) {
+            let parts: Vec<String> = values.iter().map(|v| v.to_sortable_key_part()).collect();
+            assert!(parts.windows(2).all(|w| w[0] < w[1]), "{:?}", parts);
+            assert!(parts.iter().all(|p| p.len() == parts[0].len()));
+            for (v, p) in values.iter().zip(&parts) {
+                assert_eq!(&T::from_sortable_key_part(p).unwrap(), v);
+            }
+        }
+
+        check(&[0u8, 9, 10, 255]);
+        check(&[0u64, 9, 10, u64::MAX]);
+        check(&[i32::MIN, -10, -9, -1, 0, 1, 9, 10, i32::MAX]);
+        check(&[i64::MIN, -1, 0, i64::MAX]);
+        check(&[f64::NEG_INFINITY, -10.5, -9.0, -0.0, 0.0, 1e-9, 9.0, 10.5, f64::INFINITY]);
+        check(&[-1.5f32, 0.0, 2.25]);
+        assert_eq!(9u16.to_sortable_key_part(), "00009");
+        assert_eq!((-1i8).to_sortable_key_part(), "127");
+        assert!(matches!(u8::from_sortable_key_part("x"), Err(DeezError::InvalidNumber { .. })));
+    }
+
+    #[test]
+    fn epoch() {
+        let values = [-9_999_999_999, -1_000, -11, -10, -9, -1, 0, 1, 9, 10, 1_700_000_000];
+        let parts: Vec<String> = values.iter().map(|v| epoch_key_part(*v, 10)).collect();
+        assert!(parts.windows(2).all(|w| w[0] < w[1]), "{:?}", parts);
+        for (v, p) in values.iter().zip(&parts) {
+            assert_eq!(parse_epoch_key_part(p, 10), Some(*v));
+        }
+        assert_eq!(epoch_key_part(42, 10), "0000000042");
+        assert_eq!(epoch_key_part(-1, 10), "-9999999999");
+        for s in ["", "-", "x", "--1", "+1", "1.5"] {
+            assert_eq!(parse_epoch_key_part(s, 10), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn unpad() {
+        assert_eq!(unpad_key_part("00042"), "42");
+        assert_eq!(unpad_key_part("000"), "0");
+        assert_eq!(unpad_key_part("00.5"), "0.5");
+        assert_eq!(unpad_key_part("42"), "42");
+        assert_eq!(unpad_key_part("abc"), "abc");
+    }
+
+    #[test]
+    fn read() {
+        for (value, delimiter) in [("a#b", "#"), ("a\\#", "#"), ("a||||b|", "||"), ("é#ü", "#")] {
+            let key = format!("x{}{}{}y", delimiter, escape_key_part(value, delimiter), delimiter);
+            let mut reader = KeyReader::new(&key);
+            reader.literal("x").unwrap();
+            reader.literal(delimiter).unwrap();
+            assert_eq!(reader.value(delimiter), value);
+            reader.literal(delimiter).unwrap();
+            assert_eq!(reader.value(delimiter), "y");
+            assert!(reader.is_empty());
+            reader.finish().unwrap();
+        }
+
+        let mut reader = KeyReader::new("x#y");
+        assert!(matches!(reader.literal("y"), Err(DeezError::InvalidKey { .. })));
+        assert_eq!(reader.value(""), "x#y");
+        assert!(KeyReader::new("x").finish().is_err());
+    }
+}
@@ -5,9 +5,25 @@
 
 // todo: translate README to rustdoc
 
+mod convert;
 mod deez;
+mod error;
+mod key;
 mod mocks;
 mod macros;
+mod number;
+mod time;
 
-pub use crate::deez::{IndexKey, IndexKeys, Key};
+pub use crate::convert::{FromAttributeValue, IntoAttributeValue, SetAttributeValue};
+#[doc(hidden)]
+pub use crate::convert::{check_numbers, EntityDefault, WithDefault, WithoutDefault};
+pub use crate::deez::{IndexKey, IndexKeys, Key, KeyCondition, KeyType, RangeCondition};
+pub use crate::error::DeezError;
+pub use crate::key::SortableKeyPart;
+#[doc(hidden)]
+pub use crate::deez::KeyType as DeezKeyType;
+#[doc(hidden)]
+pub use crate::key::{epoch_key_part, escape_key_part, parse_epoch_key_part, unpad_key_part, KeyReader};
+pub use crate::number::Number;
+pub use crate::time::Timestamp;
 pub use deez_derive::Deez;
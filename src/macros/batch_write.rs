@@ -55,8 +55,7 @@ macro_rules! batch_write {
             let dr = WriteRequest::builder()
                 .delete_request(
                     DeleteRequest::builder()
-                        .key(keys.hash.field(), keys.hash.av())
-                        .key(keys.range.field(), keys.range.av())
+                        .set_key(Some(keys.key()))
                         .build(),
                 )
                 .build();
@@ -22,14 +22,18 @@ macro_rules! create {
         let inst = $inst;
         let inst_keys = inst.primary_keys();
 
+        let mut condition = "attribute_not_exists(#pk)".to_string();
+        let mut names = HashMap::from([("#pk".to_string(), inst_keys.hash.field())]);
+        if let Some(range) = &inst_keys.range {
+            condition.push_str(" AND attribute_not_exists(#sk)");
+            names.insert("#sk".to_string(), range.field());
+        }
+
         $client
             .put_item()
             .table_name(inst.table__name())
-            .condition_expression("attribute_not_exists(#pk) AND attribute_not_exists(#sk)")
-            .set_expression_attribute_names(Some(HashMap::from([
-                ("#pk".to_string(), inst_keys.hash.field()),
-                ("#sk".to_string(), inst_keys.range.field()),
-            ])))
+            .condition_expression(condition)
+            .set_expression_attribute_names(Some(names))
             .set_item(Some(inst.into()))
             .send()
             .await
@@ -1,8 +1,16 @@
 /// Convenience macro for converting a query result back to `Vec<T>`.
+///
+/// # Panics
+///
+/// Panics if the output has no items or an item cannot be converted. Use
+/// [`try_vec_from_query!`] to get a [`DeezError`] instead.
+///
+/// [`DeezError`]: crate::DeezError
 /// 
 /// # Examples
 /// 
-/// ```
+/// ```ignore
+/// let range = keys.range.as_ref().unwrap();
 /// let tasks = vec_from_query!(
 ///     client
 ///         .query()
@@ -10,11 +18,11 @@
 ///         .key_condition_expression("#pk = :pk and begins_with(#sk, :sk)")
 ///         .set_expression_attribute_names(Some(HashMap::from([
 ///             ("#pk".to_string(), keys.hash.field()),
-///             ("#sk".to_string(), keys.range.field()),
+///             ("#sk".to_string(), range.field()),
 ///         ])))
 ///         .set_expression_attribute_values(Some(HashMap::from([
 ///             (":pk".to_string(), keys.hash.av()),
-///             (":sk".to_string(), keys.range.av()),
+///             (":sk".to_string(), range.av()),
 ///         ])))
 ///         .send()
 ///         .await?
@@ -25,6 +33,39 @@
 #[macro_export]
 macro_rules! vec_from_query {
     ($q:expr => $i:ident) => {{
-        $i::from($q.items().unwrap()).items()
+        $i::try_from($q.items().unwrap()).unwrap().items()
+    }};
+}
+
+/// Fallible counterpart of [`vec_from_query!`], returning a [`DeezError`] that
+/// reports the index of the first item that could not be converted instead of
+/// panicking.
+///
+/// [`DeezError`]: crate::DeezError
+///
+/// # Examples
+///
+/// ```ignore
+/// let tasks = try_vec_from_query!(
+///     client
+///         .query()
+///         .table_name(Task::table_name())
+///         .key_condition_expression("#pk = :pk")
+///         .set_expression_attribute_names(Some(HashMap::from([
+///             ("#pk".to_string(), keys.hash.field()),
+///         ])))
+///         .set_expression_attribute_values(Some(HashMap::from([
+///             (":pk".to_string(), keys.hash.av()),
+///         ])))
+///         .send()
+///         .await?
+///
+///     => TaskItems
+/// )?;
+/// ```
+#[macro_export]
+macro_rules! try_vec_from_query {
+    ($q:expr => $i:ident) => {{
+        $i::try_from($q.items().unwrap_or_default()).map(|i| i.items())
     }};
 }
@@ -20,18 +20,19 @@ macro_rules! remove {
         let ent = $ent;
         let keys = ent.primary_keys();
 
+        let mut condition = "attribute_exists(#pk)".to_string();
+        let mut names = HashMap::from([("#pk".to_string(), keys.hash.field())]);
+        if let Some(range) = &keys.range {
+            condition.push_str(" AND attribute_exists(#sk)");
+            names.insert("#sk".to_string(), range.field());
+        }
+
         $client
             .delete_item()
             .table_name(ent.table__name())
-            .condition_expression("attribute_exists(#pk) AND attribute_exists(#sk)")
-            .set_expression_attribute_names(Some(HashMap::from([
-                ("#pk".to_string(), keys.hash.field()),
-                ("#sk".to_string(), keys.range.field()),
-            ])))
-            .set_key(Some(HashMap::from([
-                (keys.hash.field(), keys.hash.av()),
-                (keys.range.field(), keys.range.av()),
-            ])))
+            .condition_expression(condition)
+            .set_expression_attribute_names(Some(names))
+            .set_key(Some(keys.key()))
             .send()
             .await
     }};
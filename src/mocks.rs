@@ -5,7 +5,12 @@ pub mod mocks {
     use aws_sdk_dynamodb::types::AttributeValue;
     use aws_sdk_dynamodb::Client;
     use aws_smithy_types::Blob;
-    use std::collections::HashMap;
+    #[cfg(feature = "rust_decimal")]
+    use rust_decimal::Decimal;
+    use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+    use std::time::{SystemTime, UNIX_EPOCH};
+    #[cfg(feature = "chrono")]
+    use chrono::{DateTime, NaiveDate, Utc};
 
     pub async fn make_client() -> Client {
         Client::new(
@@ -41,6 +46,24 @@ pub mod mocks {
         #[deez_vec(dynamo_type = "set")]
         pub num_opt_set: Option<Vec<f64>>,
 
+        pub int: i64,
+        pub int_opt: Option<u32>,
+        #[deez_vec(dynamo_type = "list")]
+        pub int_list: Vec<u8>,
+        #[deez_vec(dynamo_type = "list")]
+        pub int_opt_list: Option<Vec<i16>>,
+        #[deez_vec(dynamo_type = "set")]
+        pub int_set: Vec<u128>,
+        #[deez_vec(dynamo_type = "set")]
+        pub int_opt_set: Option<Vec<isize>>,
+
+        pub number: Number,
+        pub number_opt: Option<Number>,
+        #[deez_vec(dynamo_type = "list")]
+        pub number_list: Vec<Number>,
+        #[deez_vec(dynamo_type = "set")]
+        pub number_set: Vec<Number>,
+
         pub boolean: bool,
         pub boolean_opt: Option<bool>,
         #[deez_vec(dynamo_type = "list")]
@@ -65,6 +88,16 @@ pub mod mocks {
         pub bar_list: Vec<Bar>,
         #[deez_vec(dynamo_type = "list")]
         pub bar_opt_list: Option<Vec<Bar>>,
+
+        pub string_map: HashMap<String, String>,
+        pub int_map: BTreeMap<String, i64>,
+        pub number_opt_map: Option<HashMap<String, Number>>,
+        pub bar_map: BTreeMap<String, Bar>,
+
+        pub string_hash_set: HashSet<String>,
+        pub int_btree_set: BTreeSet<u16>,
+        pub number_opt_hash_set: Option<HashSet<Number>>,
+        pub blob_hash_set: HashSet<Blob>,
     }
 
     impl Default for Buss {
@@ -84,6 +117,19 @@ pub mod mocks {
                 num_set: vec![64.0],
                 num_opt_set: Some(vec![70.0]),
 
+                int: -9007199254740993,
+                int_opt: Some(u32::MAX),
+                int_list: vec![0, 255],
+                int_opt_list: Some(vec![-1]),
+                // the largest `u128` has 39 digits, one more than DynamoDB allows
+                int_set: vec![u128::MAX / 10],
+                int_opt_set: Some(vec![isize::MIN]),
+
+                number: "12345678901234567890123456789012345678".parse().unwrap(),
+                number_opt: Some("0.1".parse().unwrap()),
+                number_list: vec![Number::from(1), "-1.5E-7".parse().unwrap()],
+                number_set: vec!["19.99".parse().unwrap()],
+
                 boolean: true,
                 boolean_opt: Some(true),
                 boolean_list: vec![true, false],
@@ -100,10 +146,29 @@ pub mod mocks {
                 bar_opt: Some(Bar::default()),
                 bar_list: vec![Bar::default()],
                 bar_opt_list: Some(vec![Bar::default()]),
+
+                string_map: HashMap::from([("en".to_string(), "a".to_string())]),
+                int_map: BTreeMap::from([("a".to_string(), 1), ("b".to_string(), -2)]),
+                number_opt_map: Some(HashMap::from([("a".to_string(), Number::from(3))])),
+                bar_map: BTreeMap::from([("a".to_string(), Bar::default())]),
+
+                string_hash_set: HashSet::from(["a".to_string(), "b".to_string()]),
+                int_btree_set: BTreeSet::from([3, 1, 2]),
+                number_opt_hash_set: Some(HashSet::from([Number::from(1)])),
+                blob_hash_set: HashSet::from([Blob::new([1])]),
             }
         }
     }
 
+    #[cfg(feature = "rust_decimal")]
+    #[derive(Debug, Deez, Default)]
+    pub struct Price {
+        pub amount: Decimal,
+        pub amount_opt: Option<Decimal>,
+        #[deez_vec(dynamo_type = "set")]
+        pub amount_set: Vec<Decimal>,
+    }
+
     #[derive(Debug, Deez)]
     pub struct Baz {
         pub baz_string_1: String,
@@ -138,7 +203,7 @@ pub mod mocks {
         }
     }
 
-    #[derive(Debug, Deez, Clone)]
+    #[derive(Debug, Deez)]
     #[deez_schema(table = "TaskTable", service = "TaskService", entity = "Task")]
     #[deez_schema(primary_hash = "pk", primary_range = "sk")]
     #[deez_schema(gsi1_name = "task_gsi1", gsi1_hash = "gsi1pk", gsi1_range = "gsi1sk")]
@@ -172,4 +237,374 @@ pub mod mocks {
             }
         }
     }
+
+    #[derive(Debug, Deez, Clone)]
+    #[deez_schema(table = "ReminderTable", service = "ReminderService", entity = "Reminder")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    pub struct Reminder {
+        #[deez_primary(key = "hash")]
+        pub reminder_id: Option<String>,
+        #[deez_primary(key = "range")]
+        #[deez_time(format = "epoch_millis")]
+        pub remind_at: Option<SystemTime>,
+        pub created_at: SystemTime,
+        #[deez_time(format = "epoch_seconds")]
+        pub expires_at: Option<SystemTime>,
+    }
+
+    impl Default for Reminder {
+        fn default() -> Self {
+            Reminder {
+                reminder_id: None,
+                remind_at: None,
+                created_at: UNIX_EPOCH,
+                expires_at: None,
+            }
+        }
+    }
+
+    #[cfg(feature = "chrono")]
+    #[derive(Debug, Deez, Clone, Default)]
+    #[deez_schema(table = "EventTable", service = "EventService", entity = "Event")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    pub struct Event {
+        #[deez_primary(key = "hash")]
+        pub calendar: Option<String>,
+        #[deez_primary(key = "range")]
+        pub starts_at: Option<DateTime<Utc>>,
+        pub due_on: Option<NaiveDate>,
+        #[deez_time(format = "epoch_millis")]
+        #[deez_vec(dynamo_type = "set")]
+        pub reminders: Vec<DateTime<Utc>>,
+    }
+
+    #[derive(Debug, Deez, Clone, PartialEq)]
+    pub enum Status {
+        #[deez_variant(rename = "open")]
+        Open,
+        #[deez_variant(rename = "in_progress")]
+        InProgress,
+        Done,
+    }
+
+    impl std::fmt::Display for Status {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            match self {
+                Status::Open => write!(f, "Open"),
+                Status::InProgress => write!(f, "In progress"),
+                Status::Done => write!(f, "Done"),
+            }
+        }
+    }
+
+    #[derive(Debug, Deez, Clone, PartialEq)]
+    pub enum Shape {
+        Point,
+        Circle { radius: f64, label: Option<String> },
+        Label(String),
+    }
+
+    #[derive(Debug, Deez)]
+    #[deez_enum(tag = "kind")]
+    pub enum Activity {
+        Idle,
+        Comment { text: String, edited: bool },
+        Attachment(Baz),
+    }
+
+    #[derive(Debug, Deez)]
+    #[deez_schema(table = "TicketTable", service = "TicketService", entity = "Ticket")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    #[deez_schema(gsi1_name = "ticket_gsi1", gsi1_hash = "gsi1pk", gsi1_range = "gsi1sk")]
+    pub struct Ticket {
+        #[deez_primary(key = "hash")]
+        pub ticket_id: Option<String>,
+        #[deez_gsi1(key = "hash")]
+        pub project: Option<String>,
+        #[deez_gsi1(key = "range")]
+        pub status: Option<Status>,
+        pub shape: Shape,
+        #[deez_vec(dynamo_type = "list")]
+        pub history: Vec<Status>,
+        pub activity: Option<Activity>,
+    }
+
+    impl Default for Ticket {
+        fn default() -> Self {
+            Ticket {
+                ticket_id: Some("t1".to_string()),
+                project: Some("deez".to_string()),
+                status: Some(Status::InProgress),
+                shape: Shape::Point,
+                history: vec![Status::Open, Status::InProgress],
+                activity: None,
+            }
+        }
+    }
+
+    #[derive(Debug, Deez, Default)]
+    #[deez_schema(table = "LegacyTable", service = "LegacyService", entity = "Legacy")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    #[deez_schema(rename_all = "camelCase")]
+    pub struct Legacy {
+        #[deez_primary(key = "hash")]
+        pub legacy_id: Option<String>,
+        #[deez_primary(key = "range")]
+        #[deez_field(rename = "created")]
+        pub created_at: Option<String>,
+        #[deez_field(alias = "display_name")]
+        pub display_name: String,
+        pub r#type: String,
+        pub owner: LegacyOwner,
+        #[deez_default]
+        pub version: u32,
+        #[deez_default = "default_region"]
+        pub region: String,
+    }
+
+    pub fn default_region() -> String {
+        "us-east-1".to_string()
+    }
+
+    #[derive(Debug, Deez, Default)]
+    #[deez_schema(rename_all = "SCREAMING_SNAKE_CASE")]
+    pub struct LegacyOwner {
+        pub user_name: String,
+    }
+
+    #[derive(Debug, Clone, PartialEq)]
+    pub struct UserId(pub u64);
+
+    impl IntoAttributeValue for UserId {
+        fn to_av(&self) -> AttributeValue {
+            AttributeValue::S(format!("user-{}", self.0))
+        }
+    }
+
+    impl FromAttributeValue for UserId {
+        fn from_av(av: &AttributeValue) -> Result<UserId, DeezError> {
+            let s = String::from_av(av)?;
+            match s.strip_prefix("user-").and_then(|n| n.parse().ok()) {
+                Some(n) => Ok(UserId(n)),
+                None => Err(DeezError::InvalidValue {
+                    entity: String::new(),
+                    field: String::new(),
+                    value: s,
+                    reason: "expected `user-<n>`".to_string(),
+                }),
+            }
+        }
+    }
+
+    pub type Tags = Vec<std::string::String>;
+
+    #[derive(Debug, Deez, Default)]
+    #[deez_schema(table = "ProfileTable", service = "ProfileService", entity = "Profile")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    pub struct Profile {
+        #[deez_primary(key = "hash")]
+        pub user_id: Option<UserId>,
+        pub name: std::string::String,
+        pub tags: Tags,
+        pub friends: Vec<UserId>,
+    }
+
+    #[derive(Debug, Deez, Default)]
+    #[deez_schema(table = "TaskTable", service = "TaskService", entity = "Assignment")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    #[deez_index(name = "by_project", index = "task_gsi1", hash = "gsi1pk", range = "gsi1sk")]
+    #[deez_index(name = "by_employee", index = "task_gsi2", hash = "gsi2pk", range = "gsi2sk")]
+    pub struct Assignment {
+        #[deez_primary(key = "hash")]
+        #[deez_key(index = "by_project", key = "range", position = 1)]
+        pub assignment_id: Option<String>,
+        #[deez_key(index = "by_project", key = "hash")]
+        #[deez_key(index = "by_employee", key = "range")]
+        pub project: Option<String>,
+        #[deez_key(index = "by_project", key = "range")]
+        #[deez_key(index = "by_employee", key = "hash")]
+        pub employee: Option<String>,
+    }
+
+    #[derive(Debug, Deez, Default)]
+    #[deez_schema(table = "TaskTable", service = "TaskService", entity = "Audit")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    #[deez_index(name = "by_actor", index = "lsi1", range = "lsi1sk", local = true)]
+    pub struct Audit {
+        #[deez_primary(key = "hash")]
+        pub task_id: Option<String>,
+        #[deez_primary(key = "range")]
+        #[deez_key(index = "by_actor", key = "range", position = 1)]
+        pub audit_id: Option<String>,
+        #[deez_key(index = "by_actor", key = "range")]
+        pub actor: Option<String>,
+        #[deez_field(skip_none)]
+        pub note: Option<String>,
+    }
+
+    #[derive(Debug, Deez, Default)]
+    #[deez_schema(table = "AccountTable", service = "AccountService", entity = "Account")]
+    #[deez_schema(primary_hash = "pk")]
+    #[deez_schema(gsi1_name = "account_gsi1", gsi1_hash = "gsi1pk")]
+    #[deez_index(name = "by_referrer", index = "account_gsi2", hash = "gsi2pk", range = "gsi2sk", sparse = true)]
+    #[deez_schema(skip_none)]
+    pub struct Account {
+        #[deez_primary(key = "hash")]
+        #[deez_key(index = "by_referrer", key = "range")]
+        pub account_id: Option<String>,
+        #[deez_gsi1(key = "hash")]
+        pub email: Option<String>,
+        #[deez_key(index = "by_referrer", key = "hash")]
+        pub referrer: Option<String>,
+        pub nickname: Option<String>,
+    }
+
+    pub type Names = HashSet<String>;
+
+    #[derive(Debug, Deez, Default)]
+    pub struct Basket {
+        pub names: HashSet<String>,
+        pub counts: BTreeSet<u16>,
+        pub blobs: HashSet<Blob>,
+        pub numbers: Option<HashSet<Number>>,
+        #[deez_vec(dynamo_type = "set")]
+        pub tags: Vec<String>,
+        #[deez_vec(dynamo_type = "set")]
+        pub prices: Option<Vec<f64>>,
+        pub notes: Vec<String>,
+        #[deez_field(empty = "omit")]
+        pub items: Vec<String>,
+        #[deez_field(empty = "null")]
+        pub extras: HashMap<String, String>,
+        pub qualified: std::collections::HashSet<String>,
+        pub levels: Option<std::collections::BTreeSet<u32>>,
+        pub aliased: Names,
+        pub lines: std::vec::Vec<String>,
+    }
+
+    // neither the entity nor `UserId` implement `Default`
+    #[derive(Debug, Deez)]
+    #[deez_schema(table = "TaskTable", service = "TaskService", entity = "Session")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    pub struct Session {
+        #[deez_primary(key = "hash")]
+        pub session_id: Option<String>,
+        pub user_id: UserId,
+        #[deez_ignore(ignore)]
+        pub cache: Vec<String>,
+        #[deez_ignore(ignore)]
+        #[deez_default = "default_region"]
+        pub region: String,
+    }
+
+    #[derive(Debug, Deez)]
+    #[deez_schema(table = "TaskTable", service = "TaskService", entity = "Membership")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    pub struct Membership {
+        #[deez_primary(key = "hash")]
+        pub org_id: String,
+        #[deez_primary(key = "range")]
+        pub status: Status,
+        #[deez_primary(key = "range", position = 1)]
+        pub seat: u16,
+        #[deez_primary(key = "range", position = 2)]
+        pub user_id: Option<UserId>,
+    }
+
+    #[derive(Debug, Deez)]
+    #[deez_schema(table = "LegacyTable", service = "LegacyService", entity = "OrgUser")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    #[deez_schema(primary_hash_template = "ORG#${org_id}")]
+    #[deez_schema(primary_range_template = "USER#${user_id}#PROFILE")]
+    #[deez_index(name = "by_email", index = "gsi1", hash = "gsi1pk", range = "gsi1sk", hash_template = "EMAIL#${email}")]
+    #[deez_index(name = "by_org_email", index = "gsi2", hash = "gsi2pk", hash_template = "ORG-${org_id}-EMAIL-${email}")]
+    pub struct OrgUser {
+        pub org_id: String,
+        pub user_id: Option<UserId>,
+        pub email: Option<String>,
+        #[deez_key(index = "by_email", key = "range")]
+        pub name: Option<String>,
+    }
+
+    #[derive(Debug, Deez)]
+    #[deez_schema(table = "ContactTable", service = "ContactService", entity = "Contact")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    #[deez_schema(key_delimiter = "|", key_label_separator = ":", key_casing = "lower")]
+    #[deez_index(name = "by_email", index = "contact_gsi1", hash = "gsi1pk", hash_template = "EMAIL#${email}")]
+    pub struct Contact {
+        #[deez_primary(key = "hash")]
+        pub email: String,
+        #[deez_primary(key = "range")]
+        pub list: Option<String>,
+        #[deez_primary(key = "range", position = 1)]
+        pub name: Option<String>,
+    }
+
+    #[derive(Debug, Deez)]
+    #[deez_schema(table = "TagTable", service = "TagService", entity = "Tag")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    #[deez_schema(key_prefix = false, key_casing = "upper")]
+    pub struct Tag {
+        #[deez_primary(key = "hash")]
+        pub name: String,
+        #[deez_primary(key = "range")]
+        pub owner: Option<String>,
+        #[deez_primary(key = "range", position = 1)]
+        pub scope: Option<String>,
+    }
+
+    #[derive(Debug, Deez)]
+    #[deez_schema(table = "MetricTable", service = "MetricService", entity = "Reading")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    #[deez_index(name = "by_delta", index = "reading_gsi1", hash = "gsi1pk", range = "gsi1sk")]
+    pub struct Reading {
+        #[deez_primary(key = "hash")]
+        #[deez_key(index = "by_delta", key = "hash")]
+        pub sensor: String,
+        #[deez_primary(key = "range", pad = 6)]
+        pub sequence: u32,
+        #[deez_key(index = "by_delta", key = "range", sortable)]
+        pub delta: i32,
+        #[deez_key(index = "by_delta", key = "range", position = 1, sortable)]
+        pub value: f64,
+        #[deez_primary(key = "range", position = 1, time = "epoch_millis")]
+        pub taken_at: Option<SystemTime>,
+    }
+
+    #[derive(Debug, Deez)]
+    #[deez_schema(table = "GameTable", service = "GameService", entity = "Score")]
+    #[deez_schema(primary_hash = "pk", primary_range = "sk")]
+    #[deez_index(name = "by_points", index = "score_gsi1", hash = "gsi1pk", range = "gsi1sk", range_type = "number")]
+    #[deez_index(name = "by_fingerprint", index = "score_gsi2", hash = "gsi2pk", hash_type = "binary")]
+    #[deez_index(name = "by_time", index = "score_gsi3", hash = "gsi3pk", hash_type = "number")]
+    pub struct Score {
+        #[deez_primary(key = "hash")]
+        #[deez_key(index = "by_points", key = "hash")]
+        pub game: String,
+        #[deez_primary(key = "range")]
+        pub player: String,
+        #[deez_key(index = "by_points", key = "range")]
+        pub points: Option<i64>,
+        #[deez_key(index = "by_fingerprint", key = "hash")]
+        pub fingerprint: Option<Blob>,
+        #[deez_key(index = "by_time", key = "hash", time = "epoch_seconds")]
+        pub scored_at: Option<SystemTime>,
+    }
+
+    // the SDK's `KeyType`, imported to create tables, must not break number keys
+    mod sdk_key_type {
+        use crate::*;
+        use aws_sdk_dynamodb::types::{AttributeValue, KeyType};
+        use std::collections::HashMap;
+
+        #[derive(Debug, Deez)]
+        #[deez_schema(table = "GameTable", service = "GameService", entity = "Level")]
+        #[deez_schema(primary_hash = "pk", primary_range = "sk", primary_range_type = "number")]
+        pub struct Level {
+            #[deez_primary(key = "hash")]
+            pub game: String,
+            #[deez_primary(key = "range")]
+            pub number: u32,
+        }
+    }
 }
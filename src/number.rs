@@ -0,0 +1,310 @@
+use crate::DeezError;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+/// Lossless DynamoDB number, holding the exact decimal string of an
+/// `AttributeValue::N`.
+///
+/// Values are validated against DynamoDB's limits (38 significant digits,
+/// magnitude between 1E-130 and 1E+126) when constructed, so a `Number` can
+/// always be written without being rejected or rounded.
+///
+/// Numbers compare and hash by value, so `1` and `1.0` are equal, as they are
+/// in DynamoDB number sets.
+///
+/// # Examples
+///
+/// ```
+/// # use deez::Number;
+/// # fn main() -> Result<(), deez::DeezError> {
+/// let price: Number = "19.99".parse()?;
+/// let id = Number::try_from("12345678901234567890123456789012345678")?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Number(String);
+
+impl Number {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    pub fn into_string(self) -> String {
+        self.0
+    }
+
+    /// Sign, significant digits and exponent of the leading digit, with zero
+    /// as `(false, "", 0)`.
+    fn normalized(&self) -> (bool, String, i64) {
+        let s = self.0.as_str();
+        let (mantissa, exponent) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], s[i + 1..].parse::<i64>().unwrap_or(0)),
+            None => (s, 0),
+        };
+        let negative = mantissa.starts_with('-');
+        let mantissa = mantissa.strip_prefix(['+', '-']).unwrap_or(mantissa);
+        let (int, frac) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+        let digits = format!("{}{}", int, frac);
+        match digits.bytes().position(|b| b != b'0') {
+            Some(leading) => {
+                let significant = digits[leading..].trim_end_matches('0').to_string();
+                let magnitude = int.len() as i64 - leading as i64 - 1 + exponent;
+                (negative, significant, magnitude)
+            }
+            None => (false, String::new(), 0),
+        }
+    }
+
+    fn validate(s: &str) -> Result<(), &'static str> {
+        let (mantissa, exponent) = match s.find(['e', 'E']) {
+            Some(i) => (&s[..i], Some(&s[i + 1..])),
+            None => (s, None),
+        };
+        let mantissa = mantissa.strip_prefix(['+', '-']).unwrap_or(mantissa);
+        let (int, frac) = match mantissa.split_once('.') {
+            Some((int, frac)) => (int, frac),
+            None => (mantissa, ""),
+        };
+
+        if int.is_empty() && frac.is_empty() {
+            return Err("missing digits");
+        }
+        if !int.bytes().chain(frac.bytes()).all(|b| b.is_ascii_digit()) {
+            return Err("not a decimal number");
+        }
+
+        let exponent = match exponent {
+            Some(e) => {
+                let digits = e.strip_prefix(['+', '-']).unwrap_or(e);
+                if digits.is_empty() || !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err("invalid exponent");
+                }
+                e.parse::<i64>().map_err(|_| "exponent out of range")?
+            }
+            None => 0,
+        };
+
+        let digits = format!("{}{}", int, frac);
+        let leading = match digits.bytes().position(|b| b != b'0') {
+            Some(i) => i,
+            // zero
+            None => return Ok(()),
+        };
+        let trailing = digits.bytes().rev().position(|b| b != b'0').unwrap();
+
+        if digits.len() - leading - trailing > 38 {
+            return Err("more than 38 significant digits");
+        }
+
+        // exponent of the leading significant digit in scientific notation
+        let magnitude = int.len() as i64 - leading as i64 - 1 + exponent;
+        if !(-130..=125).contains(&magnitude) {
+            return Err("magnitude out of range");
+        }
+
+        Ok(())
+    }
+}
+
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        self.0 == other.0 || self.normalized() == other.normalized()
+    }
+}
+
+impl Eq for Number {}
+
+impl Hash for Number {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.normalized().hash(state);
+    }
+}
+
+impl TryFrom<String> for Number {
+    type Error = DeezError;
+
+    fn try_from(value: String) -> Result<Number, DeezError> {
+        match Number::validate(&value) {
+            Ok(()) => Ok(Number(value)),
+            Err(reason) => Err(DeezError::NumberValidation { value, reason }),
+        }
+    }
+}
+
+impl TryFrom<&str> for Number {
+    type Error = DeezError;
+
+    fn try_from(value: &str) -> Result<Number, DeezError> {
+        Number::try_from(value.to_string())
+    }
+}
+
+impl TryFrom<f64> for Number {
+    type Error = DeezError;
+
+    fn try_from(value: f64) -> Result<Number, DeezError> {
+        Number::try_from(value.to_string())
+    }
+}
+
+impl FromStr for Number {
+    type Err = DeezError;
+
+    fn from_str(s: &str) -> Result<Number, DeezError> {
+        Number::try_from(s)
+    }
+}
+
+impl fmt::Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<Number> for String {
+    fn from(value: Number) -> String {
+        value.0
+    }
+}
+
+macro_rules! number_from_int {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Number {
+                fn from(value: $t) -> Number {
+                    Number(value.to_string())
+                }
+            }
+        )*
+    };
+}
+
+// i128 and u128 can exceed 38 digits and go through `TryFrom<String>` instead
+number_from_int!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+#[cfg(feature = "rust_decimal")]
+impl From<rust_decimal::Decimal> for Number {
+    fn from(value: rust_decimal::Decimal) -> Number {
+        // at most 29 significant digits with a scale of at most 28
+        Number(value.to_string())
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl TryFrom<&Number> for rust_decimal::Decimal {
+    type Error = DeezError;
+
+    fn try_from(value: &Number) -> Result<rust_decimal::Decimal, DeezError> {
+        rust_decimal::Decimal::from_str_exact(&value.0)
+            .or_else(|_| rust_decimal::Decimal::from_scientific(&value.0))
+            .map_err(|_| DeezError::NumberValidation {
+                value: value.0.clone(),
+                reason: "does not fit in Decimal",
+            })
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+impl TryFrom<Number> for rust_decimal::Decimal {
+    type Error = DeezError;
+
+    fn try_from(value: Number) -> Result<rust_decimal::Decimal, DeezError> {
+        rust_decimal::Decimal::try_from(&value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Number;
+
+    #[test]
+    fn valid() {
+        for s in [
+            "0",
+            "-0.0",
+            "19.99",
+            "+1",
+            ".5",
+            "5.",
+            "1E+125",
+            "9.9999999999999999999999999999999999999E+125",
+            "1e-130",
+            "-1e-130",
+            "12345678901234567890123456789012345678",
+            "1234567890123456789012345678901234567800000",
+            "0.00000000000000000000000000000000000000012345678901234567890123456789012345678",
+        ] {
+            assert_eq!(s.parse::<Number>().unwrap().as_str(), s);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        for s in [
+            "",
+            "-",
+            ".",
+            "1.2.3",
+            "abc",
+            "NaN",
+            "inf",
+            "1e",
+            "1e+",
+            "1E+126",
+            "1e-131",
+            "123456789012345678901234567890123456789",
+            "1.00000000000000000000000000000000000001",
+        ] {
+            assert!(s.parse::<Number>().is_err(), "{}", s);
+        }
+
+        assert!(Number::try_from(f64::NAN).is_err());
+        assert!(Number::try_from(f64::MAX).is_err());
+        assert!(Number::try_from(u128::MAX.to_string()).is_err());
+    }
+
+    #[test]
+    fn equality() {
+        use std::collections::HashSet;
+
+        let n = |s: &str| s.parse::<Number>().unwrap();
+        for (a, b) in [
+            ("1", "1.0"),
+            ("1", "+1"),
+            ("0", "-0.0"),
+            ("0.5", ".5"),
+            ("1500", "1.5E+3"),
+            ("-0.012", "-12e-3"),
+            ("100", "00100"),
+        ] {
+            assert_eq!(n(a), n(b), "{} {}", a, b);
+        }
+        for (a, b) in [("1", "-1"), ("1", "10"), ("0.1", "1"), ("12", "21")] {
+            assert_ne!(n(a), n(b), "{} {}", a, b);
+        }
+
+        let set: HashSet<Number> = ["1", "1.0", "1e0", "2"].into_iter().map(n).collect();
+        assert_eq!(set.len(), 2);
+        // the original text is kept
+        assert_eq!(n("1.0").as_str(), "1.0");
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn decimal() {
+        use rust_decimal::Decimal;
+        use std::str::FromStr;
+
+        let d = Decimal::from_str("-1234.5600").unwrap();
+        let n = Number::from(d);
+        assert_eq!(n.as_str(), "-1234.5600");
+        assert_eq!(Decimal::try_from(&n).unwrap(), d);
+        assert_eq!(
+            Decimal::try_from("1.5E+3".parse::<Number>().unwrap()).unwrap(),
+            Decimal::from(1500)
+        );
+        assert!(Decimal::try_from("1E+100".parse::<Number>().unwrap()).is_err());
+    }
+}
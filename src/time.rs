@@ -0,0 +1,246 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const MILLIS_PER_DAY: i64 = 86_400_000;
+
+/// Date and time types that can be used as fields, stored according to the
+/// field's `#[deez_time(format = "...")]` attribute:
+///
+/// - `iso8601` (default): `S` holding e.g. `2015-01-02T03:04:05.000Z`, with a
+///   fixed precision so values sort chronologically. The precision is
+///   milliseconds, and nanoseconds for `DateTime<Utc>`.
+/// - `epoch_seconds`: `N` holding seconds since the Unix epoch, truncating
+///   sub-second precision.
+/// - `epoch_millis`: `N` holding milliseconds since the Unix epoch, truncating
+///   sub-millisecond precision.
+///
+/// Implemented for [`SystemTime`], and for `chrono::DateTime<Utc>` and
+/// `chrono::NaiveDate` with the `chrono` feature.
+pub trait Timestamp: Sized {
+    fn to_epoch_millis(&self) -> i64;
+
+    fn from_epoch_millis(millis: i64) -> Option<Self>;
+
+    fn to_iso8601(&self) -> String {
+        format_iso8601(self.to_epoch_millis())
+    }
+
+    fn from_iso8601(s: &str) -> Option<Self> {
+        parse_iso8601(s).and_then(Self::from_epoch_millis)
+    }
+}
+
+impl Timestamp for SystemTime {
+    fn to_epoch_millis(&self) -> i64 {
+        match self.duration_since(UNIX_EPOCH) {
+            Ok(d) => d.as_millis() as i64,
+            Err(e) => -(e.duration().as_millis() as i64),
+        }
+    }
+
+    fn from_epoch_millis(millis: i64) -> Option<SystemTime> {
+        let d = Duration::from_millis(millis.unsigned_abs());
+        if millis < 0 {
+            UNIX_EPOCH.checked_sub(d)
+        } else {
+            UNIX_EPOCH.checked_add(d)
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl Timestamp for chrono::DateTime<chrono::Utc> {
+    fn to_epoch_millis(&self) -> i64 {
+        self.timestamp_millis()
+    }
+
+    fn from_epoch_millis(millis: i64) -> Option<Self> {
+        use chrono::TimeZone;
+        chrono::Utc.timestamp_millis_opt(millis).single()
+    }
+
+    fn to_iso8601(&self) -> String {
+        self.to_rfc3339_opts(chrono::SecondsFormat::Nanos, true)
+    }
+
+    fn from_iso8601(s: &str) -> Option<Self> {
+        chrono::DateTime::parse_from_rfc3339(s)
+            .ok()
+            .map(|d| d.with_timezone(&chrono::Utc))
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl Timestamp for chrono::NaiveDate {
+    fn to_epoch_millis(&self) -> i64 {
+        self.signed_duration_since(epoch_date()).num_days() * MILLIS_PER_DAY
+    }
+
+    fn from_epoch_millis(millis: i64) -> Option<Self> {
+        epoch_date().checked_add_signed(chrono::Duration::days(millis.div_euclid(MILLIS_PER_DAY)))
+    }
+
+    fn to_iso8601(&self) -> String {
+        self.format("%Y-%m-%d").to_string()
+    }
+
+    fn from_iso8601(s: &str) -> Option<Self> {
+        chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+    }
+}
+
+#[cfg(feature = "chrono")]
+fn epoch_date() -> chrono::NaiveDate {
+    chrono::NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
+}
+
+/// Formats milliseconds since the epoch as `YYYY-MM-DDTHH:MM:SS.sssZ`.
+fn format_iso8601(millis: i64) -> String {
+    let (y, m, d) = civil_from_days(millis.div_euclid(MILLIS_PER_DAY));
+    let ms = millis.rem_euclid(MILLIS_PER_DAY);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        y,
+        m,
+        d,
+        ms / 3_600_000,
+        ms / 60_000 % 60,
+        ms / 1000 % 60,
+        ms % 1000
+    )
+}
+
+/// Parses an RFC 3339 date-time (or a plain `YYYY-MM-DD` date) into
+/// milliseconds since the epoch, truncating sub-millisecond precision.
+fn parse_iso8601(s: &str) -> Option<i64> {
+    fn num(s: &str) -> Option<i64> {
+        if s.is_empty() || !s.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        s.parse().ok()
+    }
+
+    let (date, time) = match s.find(['T', 't', ' ']) {
+        Some(i) => (&s[..i], Some(&s[i + 1..])),
+        None => (s, None),
+    };
+
+    let mut parts = date.splitn(3, '-');
+    let (y, m, d) = (
+        num(parts.next()?)?,
+        num(parts.next()?)?,
+        num(parts.next()?)?,
+    );
+    let days = days_from_civil(y, m, d);
+    if civil_from_days(days) != (y, m, d) {
+        return None;
+    }
+
+    let time = match time {
+        Some(t) => t,
+        None => return Some(days * MILLIS_PER_DAY),
+    };
+
+    let (clock, offset) = if let Some(clock) = time.strip_suffix(['Z', 'z']) {
+        (clock, 0)
+    } else {
+        let i = time.rfind(['+', '-'])?;
+        let (oh, om) = time[i + 1..].split_once(':')?;
+        let (oh, om) = (num(oh)?, num(om)?);
+        if oh > 23 || om > 59 {
+            return None;
+        }
+        let offset = (oh * 60 + om) * 60_000;
+        (&time[..i], if &time[i..i + 1] == "-" { -offset } else { offset })
+    };
+
+    let (hms, frac) = match clock.split_once('.') {
+        Some((hms, frac)) => (hms, Some(frac)),
+        None => (clock, None),
+    };
+    let mut parts = hms.splitn(3, ':');
+    let (h, mi, sec) = (
+        num(parts.next()?)?,
+        num(parts.next()?)?,
+        num(parts.next()?)?,
+    );
+    if h > 23 || mi > 59 || sec > 60 {
+        return None;
+    }
+    let ms = match frac {
+        Some(f) => {
+            num(f)?;
+            f.bytes().chain(std::iter::repeat(b'0')).take(3).fold(0, |a, b| a * 10 + (b - b'0') as i64)
+        }
+        None => 0,
+    };
+
+    Some(days * MILLIS_PER_DAY + ((h * 60 + mi) * 60 + sec) * 1000 + ms - offset)
+}
+
+// http://howardhinnant.github.io/date_algorithms.html
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let doy = (153 * ((m + 9) % 12) + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (if m <= 2 { yoe + era * 400 + 1 } else { yoe + era * 400 }, m, d)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn iso8601() {
+        assert_eq!(format_iso8601(0), "1970-01-01T00:00:00.000Z");
+        assert_eq!(format_iso8601(1420167845006), "2015-01-02T03:04:05.006Z");
+        assert_eq!(format_iso8601(-1), "1969-12-31T23:59:59.999Z");
+
+        assert_eq!(parse_iso8601("2015-01-02T03:04:05.006Z"), Some(1420167845006));
+        assert_eq!(parse_iso8601("2015-01-02T03:04:05.0069Z"), Some(1420167845006));
+        assert_eq!(parse_iso8601("2015-01-02T04:04:05.6+01:00"), Some(1420167845600));
+        assert_eq!(parse_iso8601("2015-01-01T22:04:05-05:00"), Some(1420167845000));
+        assert_eq!(parse_iso8601("2015-01-02"), Some(1420156800000));
+        assert_eq!(parse_iso8601("2016-02-29"), Some(1456704000000));
+
+        for s in ["", "2015-02-29", "2015-13-01", "2015-01-02T25:00:00Z", "2015-01-02T03:04:05", "x"] {
+            assert_eq!(parse_iso8601(s), None, "{}", s);
+        }
+    }
+
+    #[test]
+    fn system_time() {
+        let t = UNIX_EPOCH - Duration::from_millis(1500);
+        assert_eq!(t.to_epoch_millis(), -1500);
+        assert_eq!(SystemTime::from_epoch_millis(-1500), Some(t));
+        assert_eq!(SystemTime::from_iso8601(&t.to_iso8601()), Some(t));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn date_time() {
+        use chrono::{DateTime, TimeZone, Utc};
+
+        let t = Utc.timestamp_opt(1420167845, 6_000_789).unwrap();
+        assert_eq!(t.to_iso8601(), "2015-01-02T03:04:05.006000789Z");
+        assert_eq!(DateTime::<Utc>::from_iso8601(&t.to_iso8601()), Some(t));
+        assert_eq!(
+            DateTime::<Utc>::from_iso8601("2015-01-02T03:04:05.006Z"),
+            Utc.timestamp_millis_opt(1420167845006).single()
+        );
+        assert_eq!(t.to_epoch_millis(), 1420167845006);
+    }
+}
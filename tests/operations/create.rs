@@ -43,6 +43,7 @@ async fn create() -> Result<()> {
         ..Default::default()
     }
     .primary_keys();
+    let task_range = task_keys.range.as_ref().unwrap();
 
     let tasks = vec_from_query!(
         client
@@ -51,11 +52,11 @@ async fn create() -> Result<()> {
             .key_condition_expression("#pk = :pk and begins_with(#sk, :sk)")
             .set_expression_attribute_names(Some(HashMap::from([
                 ("#pk".to_string(), task_keys.hash.field()),
-                ("#sk".to_string(), task_keys.range.field()),
+                ("#sk".to_string(), task_range.field()),
             ])))
             .set_expression_attribute_values(Some(HashMap::from([
                 (":pk".to_string(), task_keys.hash.av()),
-                (":sk".to_string(), task_keys.range.av()),
+                (":sk".to_string(), task_range.av()),
             ])))
             .send()
             .await?
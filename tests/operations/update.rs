@@ -37,10 +37,7 @@ async fn update() -> Result<()> {
 
     c.update_item()
         .table_name(Task::table_name())
-        .set_key(Some(HashMap::from([
-            (k.hash.field(), k.hash.av()),
-            (k.range.field(), k.range.av()),
-        ])))
+        .set_key(Some(k.key()))
         .update_expression("SET #u = :u")
         .set_expression_attribute_names(Some(HashMap::from([(
             "#u".to_string(),
@@ -60,11 +57,11 @@ async fn update() -> Result<()> {
             .key_condition_expression("#pk = :pk and begins_with(#sk, :sk)")
             .set_expression_attribute_names(Some(HashMap::from([
                 ("#pk".to_string(), k.hash.field()),
-                ("#sk".to_string(), k.range.field()),
+                ("#sk".to_string(), k.range.as_ref().unwrap().field()),
             ])))
             .set_expression_attribute_values(Some(HashMap::from([
                 (":pk".to_string(), k.hash.av()),
-                (":sk".to_string(), k.range.av()),
+                (":sk".to_string(), k.range.as_ref().unwrap().av()),
             ])))
             .send()
             .await?